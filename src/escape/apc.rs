@@ -0,0 +1,46 @@
+use std::fmt::{self, Display};
+
+/// Application Program Command sequences.
+///
+/// Terminals which don't recognize an APC ignore it entirely.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Apc {
+    /// A command in the Kitty graphics protocol, or the terminal's reply to one.
+    ///
+    /// `control_data` is a comma-separated list of `key=value` pairs and the `payload` is usually
+    /// base64 encoded image data. In a reply the payload is `OK` or an error message.
+    /// <https://sw.kovidgoyal.net/kitty/graphics-protocol/>
+    ///
+    /// ```
+    /// # use termina::escape::apc::Apc;
+    /// // Query support for the graphics protocol with a 1x1 RGB image.
+    /// let query = Apc::KittyGraphics {
+    ///     control_data: "i=31,s=1,v=1,a=q,t=d,f=24".to_string(),
+    ///     payload: "AAAA".to_string(),
+    /// };
+    /// assert_eq!(query.to_string(), "\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\");
+    /// ```
+    KittyGraphics {
+        control_data: String,
+        payload: String,
+    },
+}
+
+impl Display for Apc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(super::APC)?;
+        match self {
+            // APC G control-data ; payload ST
+            Self::KittyGraphics {
+                control_data,
+                payload,
+            } => {
+                write!(f, "G{control_data}")?;
+                if !payload.is_empty() {
+                    write!(f, ";{payload}")?;
+                }
+            }
+        }
+        f.write_str(super::ST)
+    }
+}
@@ -28,6 +28,7 @@ pub enum Csi {
     Keyboard(Keyboard),
     Device(Device),
     Window(Box<Window>),
+    StatusDisplay(StatusDisplay),
 }
 
 impl Display for Csi {
@@ -43,6 +44,7 @@ impl Display for Csi {
             Self::Keyboard(keyboard) => keyboard.fmt(f),
             Self::Device(device) => device.fmt(f),
             Self::Window(window) => window.fmt(f),
+            Self::StatusDisplay(status) => status.fmt(f),
         }
     }
 }
@@ -793,6 +795,60 @@ pub enum Edit {
 
     /// REP - Repeat the preceding character n times
     Repeat(u32),
+
+    /// DECFRA - Fill Rectangular Area
+    /// Fills the rectangle with the given character using the current graphic rendition.
+    /// <https://vt100.net/docs/vt510-rm/DECFRA.html>
+    FillRectangularArea { ch: char, area: Rectangle },
+
+    /// DECERA - Erase Rectangular Area
+    /// Erases all characters in the rectangle, including their attributes.
+    /// <https://vt100.net/docs/vt510-rm/DECERA.html>
+    EraseRectangularArea(Rectangle),
+
+    /// DECSERA - Selective Erase Rectangular Area
+    /// Erases the characters in the rectangle which were not protected by DECSCA.
+    /// <https://vt100.net/docs/vt510-rm/DECSERA.html>
+    SelectiveEraseRectangularArea(Rectangle),
+
+    /// DECCRA - Copy Rectangular Area
+    /// Copies the source rectangle so that its top-left corner lands at the destination.
+    /// <https://vt100.net/docs/vt510-rm/DECCRA.html>
+    CopyRectangularArea {
+        source: Rectangle,
+        source_page: OneBased,
+        destination_top: OneBased,
+        destination_left: OneBased,
+        destination_page: OneBased,
+    },
+
+    /// DECCARA - Change Attributes in Rectangular Area
+    /// Sets the given SGR attributes on the cells in the area. Which cells are affected depends
+    /// on the attribute change extent, see `Self::SelectAttributeChangeExtent`.
+    ///
+    /// Only `Sgr::Reset`, intensity, underline, blink and reverse are specified for DECCARA
+    /// although some terminals like xterm also accept italic, invisible and strike-through and
+    /// colors.
+    /// <https://vt100.net/docs/vt510-rm/DECCARA.html>
+    ChangeAttributesInRectangularArea {
+        area: Rectangle,
+        attributes: Vec<Sgr>,
+    },
+
+    /// DECRARA - Reverse Attributes in Rectangular Area
+    /// Toggles the given attributes on the cells in the area. The attributes are given as the SGR
+    /// which would turn them on, for example `Sgr::Intensity(Intensity::Bold)`, or `Sgr::Reset`
+    /// to reverse all attributes.
+    /// <https://vt100.net/docs/vt510-rm/DECRARA.html>
+    ReverseAttributesInRectangularArea {
+        area: Rectangle,
+        attributes: Vec<Sgr>,
+    },
+
+    /// DECSACE - Select Attribute Change Extent
+    /// Controls whether DECCARA and DECRARA affect a stream of characters or a rectangle.
+    /// <https://vt100.net/docs/vt510-rm/DECSACE.html>
+    SelectAttributeChangeExtent(AttributeChangeExtent),
 }
 
 impl Display for Edit {
@@ -816,10 +872,74 @@ impl Display for Edit {
             Self::ScrollUp(n) => write_csi(*n, f, "S"),
             Self::EraseInDisplay(n) => write_csi(*n as u32, f, "J"),
             Self::Repeat(n) => write_csi(*n, f, "b"),
+            Self::FillRectangularArea { ch, area } => write!(f, "{};{area}$x", *ch as u32),
+            Self::EraseRectangularArea(area) => write!(f, "{area}$z"),
+            Self::SelectiveEraseRectangularArea(area) => write!(f, "{area}${{"),
+            Self::CopyRectangularArea {
+                source,
+                source_page,
+                destination_top,
+                destination_left,
+                destination_page,
+            } => write!(
+                f,
+                "{source};{source_page};{destination_top};{destination_left};{destination_page}$v"
+            ),
+            Self::ChangeAttributesInRectangularArea { area, attributes } => {
+                write!(f, "{area}")?;
+                for sgr in attributes {
+                    write!(f, ";{sgr}")?;
+                }
+                write!(f, "$r")
+            }
+            Self::ReverseAttributesInRectangularArea { area, attributes } => {
+                write!(f, "{area}")?;
+                for sgr in attributes {
+                    write!(f, ";{sgr}")?;
+                }
+                write!(f, "$t")
+            }
+            Self::SelectAttributeChangeExtent(extent) => write!(f, "{}*x", *extent as u8),
         }
     }
 }
 
+/// A rectangle of cells used by the rectangular area operations like DECERA and DECCRA.
+///
+/// All coordinates are inclusive. Terminals clamp the bottom and right edges to the page size so
+/// `OneBased::new(u16::MAX)` can be used to mean "to the edge of the screen."
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rectangle {
+    pub top: OneBased,
+    pub left: OneBased,
+    pub bottom: OneBased,
+    pub right: OneBased,
+}
+
+impl Display for Rectangle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            top,
+            left,
+            bottom,
+            right,
+        } = self;
+        write!(f, "{top};{left};{bottom};{right}")
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeChangeExtent {
+    /// Affect the stream of character positions from the top-left position to the bottom-right
+    /// position, wrapping at the ends of lines. This is the terminal's default.
+    #[default]
+    Default = 0,
+    /// The same as `Self::Default`.
+    Stream = 1,
+    /// Affect only the character positions within the rectangle.
+    Rectangle = 2,
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum EraseInLine {
     #[default]
@@ -995,6 +1115,10 @@ pub enum DecPrivateModeCode {
     /// <https://github.com/contour-terminal/contour/blob/master/docs/vt-extensions/color-palette-update-notifications.md>
     Theme = 2031,
 
+    /// <https://gist.github.com/rockorager/e695fb2924d36b2bcf1fff4a3704bd83>
+    /// Report window resizes in-band with `CSI 48 ; height ; width ; pixel height ; pixel width t`
+    InBandResize = 2048,
+
     /// Applies to sixel and regis modes
     UsePrivateColorRegistersForEachGraphic = 1070,
 
@@ -1011,6 +1135,63 @@ pub enum DecPrivateModeCode {
     Win32InputMode = 9001,
 }
 
+impl TryFrom<u16> for DecPrivateModeCode {
+    type Error = ();
+
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        let code = match code {
+            1 => Self::ApplicationCursorKeys,
+            2 => Self::DecAnsiMode,
+            3 => Self::Select132Columns,
+            4 => Self::SmoothScroll,
+            5 => Self::ReverseVideo,
+            6 => Self::OriginMode,
+            7 => Self::AutoWrap,
+            8 => Self::AutoRepeat,
+            12 => Self::StartBlinkingCursor,
+            25 => Self::ShowCursor,
+            45 => Self::ReverseWraparound,
+            69 => Self::LeftRightMarginMode,
+            80 => Self::SixelDisplayMode,
+            1000 => Self::MouseTracking,
+            1001 => Self::HighlightMouseTracking,
+            1002 => Self::ButtonEventMouse,
+            1003 => Self::AnyEventMouse,
+            1004 => Self::FocusTracking,
+            1005 => Self::Utf8Mouse,
+            1006 => Self::SGRMouse,
+            1015 => Self::RXVTMouse,
+            1016 => Self::SGRPixelsMouse,
+            1036 => Self::XTermMetaSendsEscape,
+            1039 => Self::XTermAltSendsEscape,
+            1048 => Self::SaveCursor,
+            1049 => Self::ClearAndEnableAlternateScreen,
+            47 => Self::EnableAlternateScreen,
+            1047 => Self::OptEnableAlternateScreen,
+            2004 => Self::BracketedPaste,
+            2027 => Self::GraphemeClustering,
+            2031 => Self::Theme,
+            2048 => Self::InBandResize,
+            1070 => Self::UsePrivateColorRegistersForEachGraphic,
+            2026 => Self::SynchronizedOutput,
+            7727 => Self::MinTTYApplicationEscapeKeyMode,
+            8452 => Self::SixelScrollsRight,
+            9001 => Self::Win32InputMode,
+            _ => return Err(()),
+        };
+        Ok(code)
+    }
+}
+
+impl From<u16> for DecPrivateMode {
+    fn from(code: u16) -> Self {
+        match DecPrivateModeCode::try_from(code) {
+            Ok(code) => Self::Code(code),
+            Err(()) => Self::Unspecified(code),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TerminalMode {
     Code(TerminalModeCode),
@@ -1285,7 +1466,8 @@ impl Display for SetKeyboardFlagsMode {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Device {
-    DeviceAttributes(()),
+    /// DA1 - the terminal's reply to `Self::RequestPrimaryDeviceAttributes`.
+    DeviceAttributes(DeviceAttributes),
     /// DECSTR - <https://vt100.net/docs/vt510-rm/DECSTR.html>
     SoftReset,
     RequestPrimaryDeviceAttributes,
@@ -1301,7 +1483,7 @@ pub enum Device {
 impl Display for Device {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::DeviceAttributes(_) => unimplemented!(),
+            Self::DeviceAttributes(attributes) => write!(f, "?{attributes}c"),
             Self::SoftReset => write!(f, "!p"),
             Self::RequestPrimaryDeviceAttributes => write!(f, "c"),
             Self::RequestSecondaryDeviceAttributes => write!(f, ">c"),
@@ -1313,6 +1495,137 @@ impl Display for Device {
     }
 }
 
+/// The terminal's reply to `Device::RequestPrimaryDeviceAttributes`.
+///
+/// <https://vt100.net/docs/vt510-rm/DA1.html>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceAttributes {
+    /// The service class or conformance level of the terminal, for example `1` for a VT100,
+    /// `62` for a VT220 or `65` for a VT525.
+    pub conformance_level: u16,
+    pub attributes: DeviceAttributeFlags,
+}
+
+impl Display for DeviceAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.conformance_level)?;
+        for bit in 0..u64::BITS {
+            if self.attributes.bits() & (1 << bit) != 0 {
+                write!(f, ";{bit}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+bitflags::bitflags! {
+    /// Extensions reported in `DeviceAttributes`.
+    ///
+    /// Each flag's bit is the number of the attribute in the DA1 reply. Attributes with numbers
+    /// above 63 are not recorded.
+    /// <https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h3-Functions-using-CSI-_-ordered-by-the-final-character_s_>
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct DeviceAttributeFlags: u64 {
+        const COLUMNS_132 = 1 << 1;
+        const PRINTER = 1 << 2;
+        const REGIS_GRAPHICS = 1 << 3;
+        const SIXEL_GRAPHICS = 1 << 4;
+        const SELECTIVE_ERASE = 1 << 6;
+        const USER_DEFINED_KEYS = 1 << 8;
+        const NATIONAL_REPLACEMENT_CHARACTER_SETS = 1 << 9;
+        const TECHNICAL_CHARACTERS = 1 << 15;
+        const LOCATOR_PORT = 1 << 16;
+        const TERMINAL_STATE_INTERROGATION = 1 << 17;
+        const USER_WINDOWS = 1 << 18;
+        const HORIZONTAL_SCROLLING = 1 << 21;
+        const ANSI_COLOR = 1 << 22;
+        const RECTANGULAR_EDITING = 1 << 28;
+        const ANSI_TEXT_LOCATOR = 1 << 29;
+        /// Access to the clipboard with `Osc::SetSelection` (OSC 52).
+        const CLIPBOARD_ACCESS = 1 << 52;
+    }
+}
+
+// Status display
+
+/// Control of the status line found at the bottom of the screen on DEC terminals.
+///
+/// When the status line type is `StatusLineType::HostWritable` the application may select the
+/// status line as the active display and write to it as it would the main display. Note that the
+/// status line does not take a row from the scroll region. Also see `HostWritableStatusLine`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusDisplay {
+    /// DECSSDT - Select Status Display (Line) Type
+    /// <https://vt100.net/docs/vt510-rm/DECSSDT.html>
+    SelectType(StatusLineType),
+    /// DECSASD - Select Active Status Display
+    /// <https://vt100.net/docs/vt510-rm/DECSASD.html>
+    SelectActive(ActiveStatusDisplay),
+}
+
+impl Display for StatusDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SelectType(ty) => write!(f, "{}$~", *ty as u8),
+            Self::SelectActive(display) => write!(f, "{}$}}", *display as u8),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StatusLineType {
+    /// No status line.
+    #[default]
+    None = 0,
+    /// The terminal's own status line with information like the cursor position.
+    Indicator = 1,
+    /// A status line which the application may write to.
+    HostWritable = 2,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ActiveStatusDisplay {
+    /// The main display. This is the display used normally.
+    #[default]
+    Main = 0,
+    /// The host-writable status line.
+    StatusLine = 1,
+}
+
+/// A helper which replaces the contents of the host-writable status line.
+///
+/// This selects the status line as the active display, moves to the start of the status line and
+/// erases it, writes the content and then selects the main display again. The cursor position in
+/// the main display is not affected. The status line type must already be set to
+/// `StatusLineType::HostWritable` with `StatusDisplay::SelectType`.
+///
+/// ```
+/// # use termina::escape::csi::HostWritableStatusLine;
+/// assert_eq!(
+///     HostWritableStatusLine("NORMAL").to_string(),
+///     "\x1b[1$}\x1b[1;1H\x1b[2KNORMAL\x1b[0$}",
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostWritableStatusLine<T>(pub T);
+
+impl<T: Display> Display for HostWritableStatusLine<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}{}",
+            Csi::StatusDisplay(StatusDisplay::SelectActive(ActiveStatusDisplay::StatusLine)),
+            Csi::Cursor(Cursor::Position {
+                line: OneBased::default(),
+                col: OneBased::default(),
+            }),
+            Csi::Edit(Edit::EraseInLine(EraseInLine::EraseLine)),
+            self.0,
+            Csi::StatusDisplay(StatusDisplay::SelectActive(ActiveStatusDisplay::Main)),
+        )
+    }
+}
+
 // Window
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -1345,6 +1658,10 @@ pub enum Window {
     ReportWindowPosition,
     ReportTextAreaPosition,
     ReportTextAreaSizePixels,
+    ReportTextAreaSizePixelsResponse {
+        width: Option<i64>,
+        height: Option<i64>,
+    },
     ReportWindowSizePixels,
     ReportScreenSizePixels,
     ReportCellSizePixels,
@@ -1409,6 +1726,9 @@ impl Display for Window {
             Window::ReportWindowPosition => write!(f, "13t"),
             Window::ReportTextAreaPosition => write!(f, "13;2t"),
             Window::ReportTextAreaSizePixels => write!(f, "14t"),
+            Window::ReportTextAreaSizePixelsResponse { width, height } => {
+                write!(f, "4;{};{}t", NumstrOrEmpty(*height), NumstrOrEmpty(*width))
+            }
             Window::ReportWindowSizePixels => write!(f, "14;2t"),
             Window::ReportScreenSizePixels => write!(f, "15t"),
             Window::ReportCellSizePixels => write!(f, "16t"),
@@ -1493,6 +1813,57 @@ mod test {
             Csi::Window(Box::new(Window::PopIconAndWindowTitle)).to_string(),
         );
 
+        // Clear a pane in one sequence and then move its contents.
+        let area = Rectangle {
+            top: OneBased::new(2).unwrap(),
+            left: OneBased::new(3).unwrap(),
+            bottom: OneBased::new(10).unwrap(),
+            right: OneBased::new(40).unwrap(),
+        };
+        assert_eq!(
+            "\x1b[2;3;10;40$z",
+            Csi::Edit(Edit::EraseRectangularArea(area)).to_string()
+        );
+        assert_eq!(
+            "\x1b[2;3;10;40;1;5;3;1$v",
+            Csi::Edit(Edit::CopyRectangularArea {
+                source: area,
+                source_page: OneBased::default(),
+                destination_top: OneBased::new(5).unwrap(),
+                destination_left: OneBased::new(3).unwrap(),
+                destination_page: OneBased::default(),
+            })
+            .to_string()
+        );
+        assert_eq!(
+            "\x1b[46;2;3;10;40$x",
+            Csi::Edit(Edit::FillRectangularArea { ch: '.', area }).to_string()
+        );
+        assert_eq!(
+            "\x1b[2;3;10;40;1;4$r",
+            Csi::Edit(Edit::ChangeAttributesInRectangularArea {
+                area,
+                attributes: vec![
+                    Sgr::Intensity(Intensity::Bold),
+                    Sgr::Underline(Underline::Single)
+                ],
+            })
+            .to_string()
+        );
+        assert_eq!(
+            "\x1b[2*x",
+            Csi::Edit(Edit::SelectAttributeChangeExtent(
+                AttributeChangeExtent::Rectangle
+            ))
+            .to_string()
+        );
+
+        // Enable the host-writable status line.
+        assert_eq!(
+            "\x1b[2$~",
+            Csi::StatusDisplay(StatusDisplay::SelectType(StatusLineType::HostWritable)).to_string()
+        );
+
         // Set the cursor style to the terminal's default.
         // <https://terminalguide.namepad.de/seq/csi_sq_t_space/>
         assert_eq!(
@@ -2,6 +2,8 @@ use std::fmt::{self, Display};
 
 use crate::style::CursorStyle;
 
+use super::csi::{ActiveStatusDisplay, StatusLineType};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Dcs {
     // DECRQSS: <https://vt100.net/docs/vt510-rm/DECRQSS.html>
@@ -11,6 +13,43 @@ pub enum Dcs {
         is_request_valid: bool,
         value: DcsResponse,
     },
+    /// DECRPCRA - the terminal's reply to `csi::Window::ChecksumRectangularArea` (DECRQCRA).
+    /// <https://vt100.net/docs/vt510-rm/DECRQCRA.html>
+    RectangularAreaChecksum {
+        request_id: i64,
+        checksum: u16,
+    },
+    /// BSU - Begin Synchronized Update
+    ///
+    /// This is the original DCS form of synchronized output supported by older versions of
+    /// iTerm2 and some other terminals. Newer terminals use
+    /// `csi::DecPrivateModeCode::SynchronizedOutput` instead.
+    /// <https://gitlab.com/gnachman/iterm2/-/wikis/synchronized-updates-spec>
+    BeginSynchronizedUpdate,
+    /// ESU - End Synchronized Update
+    ///
+    /// See `Self::BeginSynchronizedUpdate`.
+    EndSynchronizedUpdate,
+    /// XTVERSION - the terminal's reply to `csi::Device::RequestTerminalNameAndVersion`.
+    ///
+    /// The format of the text is not standardized. Most terminals reply with `name(version)` or
+    /// `name version`, for example `kitty(0.36.4)` or `tmux 3.4`.
+    TerminalNameAndVersion(String),
+    /// XTGETTCAP - Request the values of terminfo capabilities by name, for example `Smulx`.
+    ///
+    /// The terminal replies with `Self::TerminfoCapability`. Some terminals reply to a request
+    /// for multiple names with a single reply so prefer requesting one name at a time.
+    /// <https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h3-Device-Control-functions>
+    RequestTerminfoCapabilities(Vec<String>),
+    /// The terminal's reply to `Self::RequestTerminfoCapabilities`.
+    ///
+    /// `value` is `None` for boolean capabilities. When the request is not valid the terminal
+    /// doesn't know the capability.
+    TerminfoCapability {
+        is_request_valid: bool,
+        name: String,
+        value: Option<String>,
+    },
 }
 
 impl Display for Dcs {
@@ -25,12 +64,54 @@ impl Display for Dcs {
                 is_request_valid,
                 value,
             } => write!(f, "{}$r{value}", if *is_request_valid { 1 } else { 0 })?,
+            // DCS Pid ! ~ D...D ST
+            Self::RectangularAreaChecksum {
+                request_id,
+                checksum,
+            } => write!(f, "{request_id}!~{checksum:04X}")?,
+            // DCS = 1 s ST
+            Self::BeginSynchronizedUpdate => f.write_str("=1s")?,
+            // DCS = 2 s ST
+            Self::EndSynchronizedUpdate => f.write_str("=2s")?,
+            // DCS > | text ST
+            Self::TerminalNameAndVersion(text) => write!(f, ">|{text}")?,
+            // DCS + q Pt ST
+            Self::RequestTerminfoCapabilities(names) => {
+                f.write_str("+q")?;
+                for (i, name) in names.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(";")?;
+                    }
+                    write_hex(f, name)?;
+                }
+            }
+            // DCS Ps + r Pt ST
+            Self::TerminfoCapability {
+                is_request_valid,
+                name,
+                value,
+            } => {
+                write!(f, "{}+r", if *is_request_valid { 1 } else { 0 })?;
+                write_hex(f, name)?;
+                if let Some(value) = value {
+                    f.write_str("=")?;
+                    write_hex(f, value)?;
+                }
+            }
         }
         // ST
         f.write_str(super::ST)
     }
 }
 
+/// XTGETTCAP encodes names and values as hexadecimal strings.
+fn write_hex(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    for byte in s.bytes() {
+        write!(f, "{byte:02X}")?;
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DcsRequest {
     ActiveStatusDisplay,
@@ -106,6 +187,10 @@ pub enum DcsResponse {
     /// SGR
     GraphicRendition(Vec<super::csi::Sgr>),
     CursorStyle(CursorStyle),
+    /// DECSSDT
+    StatusLineType(StatusLineType),
+    /// DECSASD
+    ActiveStatusDisplay(ActiveStatusDisplay),
     // There are others but adding them would mean adding a lot of parsing code...
 }
 
@@ -124,6 +209,8 @@ impl Display for DcsResponse {
                 Ok(())
             }
             Self::CursorStyle(style) => write!(f, "{style} q"),
+            Self::StatusLineType(ty) => write!(f, "{}$~", *ty as u8),
+            Self::ActiveStatusDisplay(display) => write!(f, "{}$}}", *display as u8),
         }
     }
 }
@@ -142,5 +229,24 @@ mod test {
             Dcs::Request(DcsRequest::CursorStyle).to_string(),
             "\x1bP$q q\x1b\\"
         );
+        assert_eq!(
+            Dcs::RectangularAreaChecksum {
+                request_id: 1,
+                checksum: 0xab,
+            }
+            .to_string(),
+            "\x1bP1!~00AB\x1b\\"
+        );
+        assert_eq!(Dcs::BeginSynchronizedUpdate.to_string(), "\x1bP=1s\x1b\\");
+        assert_eq!(Dcs::EndSynchronizedUpdate.to_string(), "\x1bP=2s\x1b\\");
+        assert_eq!(
+            Dcs::TerminalNameAndVersion("foot(1.18.1)".to_string()).to_string(),
+            "\x1bP>|foot(1.18.1)\x1b\\"
+        );
+        assert_eq!(
+            Dcs::RequestTerminfoCapabilities(vec!["TN".to_string(), "Smulx".to_string()])
+                .to_string(),
+            "\x1bP+q544E;536D756C78\x1b\\"
+        );
     }
 }
@@ -0,0 +1,210 @@
+// CREDIT: the set of sequences here is based on TermWiz's `Esc` and `EscCode` types:
+// <https://github.com/wezterm/wezterm/blob/a87358516004a652ad840bc1661bdf65ffc89b43/termwiz/src/escape/esc.rs>.
+// Instead of TermWiz's flat `EscCode` enum with one variant per intermediate/final byte pair
+// this module groups the character set designations into `CharacterSetSlot` and `CharacterSet`.
+
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Esc {
+    /// DECSC - Save Cursor
+    ///
+    /// Saves the cursor position, graphic rendition, character set shift state, wrap flag,
+    /// origin mode and selective erase attribute.
+    /// <https://vt100.net/docs/vt510-rm/DECSC.html>
+    SaveCursor,
+    /// DECRC - Restore Cursor
+    ///
+    /// Restores the state saved by `Self::SaveCursor`.
+    /// <https://vt100.net/docs/vt510-rm/DECRC.html>
+    RestoreCursor,
+    /// DECKPAM - Keypad Application Mode
+    ///
+    /// The auxiliary keypad sends application sequences.
+    /// <https://vt100.net/docs/vt510-rm/DECKPAM.html>
+    ApplicationKeypad,
+    /// DECKPNM - Keypad Numeric Mode
+    ///
+    /// The auxiliary keypad sends the characters printed on the keys.
+    /// <https://vt100.net/docs/vt510-rm/DECKPNM.html>
+    NumericKeypad,
+    /// RIS - Reset to Initial State
+    ///
+    /// A "hard" reset of the terminal. Also see `csi::Device::SoftReset`.
+    /// <https://vt100.net/docs/vt510-rm/RIS.html>
+    FullReset,
+    /// IND - Index
+    ///
+    /// Moves the cursor down one line in the same column, scrolling the scroll region up if the
+    /// cursor is at the bottom margin.
+    Index,
+    /// NEL - Next Line
+    ///
+    /// Moves the cursor to the first column of the next line, scrolling the scroll region up if
+    /// the cursor is at the bottom margin.
+    /// <https://vt100.net/docs/vt510-rm/NEL.html>
+    NextLine,
+    /// RI - Reverse Index
+    ///
+    /// Moves the cursor up one line in the same column, scrolling the scroll region down if the
+    /// cursor is at the top margin.
+    /// <https://vt100.net/docs/vt510-rm/RI.html>
+    ReverseIndex,
+    /// HTS - Horizontal Tab Set
+    ///
+    /// Sets a tab stop at the cursor's column.
+    /// <https://vt100.net/docs/vt510-rm/HTS.html>
+    HorizontalTabSet,
+    /// DECALN - Screen Alignment Pattern
+    ///
+    /// Fills the screen with 'E' characters. Commonly used by test suites like vttest.
+    /// <https://vt100.net/docs/vt510-rm/DECALN.html>
+    ScreenAlignmentDisplay,
+    /// DECDHL - Double-Height Line, top half
+    /// <https://vt100.net/docs/vt510-rm/DECDHL.html>
+    DoubleHeightLineTopHalf,
+    /// DECDHL - Double-Height Line, bottom half
+    /// <https://vt100.net/docs/vt510-rm/DECDHL.html>
+    DoubleHeightLineBottomHalf,
+    /// DECSWL - Single-Width Line
+    /// <https://vt100.net/docs/vt510-rm/DECSWL.html>
+    SingleWidthLine,
+    /// DECDWL - Double-Width Line
+    /// <https://vt100.net/docs/vt510-rm/DECDWL.html>
+    DoubleWidthLine,
+    /// SCS - Select Character Set
+    ///
+    /// Designates a character set into one of the G0-G3 slots. For example this is used to
+    /// switch G0 to DEC line drawing characters:
+    ///
+    /// ```
+    /// # use termina::escape::esc::{CharacterSet, CharacterSetSlot, Esc};
+    /// let line_drawing = Esc::DesignateCharacterSet {
+    ///     slot: CharacterSetSlot::G0,
+    ///     charset: CharacterSet::DecLineDrawing,
+    /// };
+    /// assert_eq!(line_drawing.to_string(), "\x1b(0");
+    /// ```
+    ///
+    /// <https://vt100.net/docs/vt510-rm/SCS.html>
+    DesignateCharacterSet {
+        slot: CharacterSetSlot,
+        charset: CharacterSet,
+    },
+}
+
+impl Display for Esc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(super::ESC)?;
+        match self {
+            Self::SaveCursor => f.write_str("7"),
+            Self::RestoreCursor => f.write_str("8"),
+            Self::ApplicationKeypad => f.write_str("="),
+            Self::NumericKeypad => f.write_str(">"),
+            Self::FullReset => f.write_str("c"),
+            Self::Index => f.write_str("D"),
+            Self::NextLine => f.write_str("E"),
+            Self::ReverseIndex => f.write_str("M"),
+            Self::HorizontalTabSet => f.write_str("H"),
+            Self::ScreenAlignmentDisplay => f.write_str("#8"),
+            Self::DoubleHeightLineTopHalf => f.write_str("#3"),
+            Self::DoubleHeightLineBottomHalf => f.write_str("#4"),
+            Self::SingleWidthLine => f.write_str("#5"),
+            Self::DoubleWidthLine => f.write_str("#6"),
+            Self::DesignateCharacterSet { slot, charset } => write!(f, "{slot}{charset}"),
+        }
+    }
+}
+
+/// One of the four character set "slots" which `Esc::DesignateCharacterSet` can designate into.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CharacterSetSlot {
+    #[default]
+    G0,
+    G1,
+    G2,
+    G3,
+}
+
+impl Display for CharacterSetSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The intermediate byte of the SCS sequence. These are the 94-character set
+        // intermediates.
+        let intermediate = match self {
+            Self::G0 => "(",
+            Self::G1 => ")",
+            Self::G2 => "*",
+            Self::G3 => "+",
+        };
+        f.write_str(intermediate)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CharacterSet {
+    /// US ASCII. This is the default character set for every slot.
+    #[default]
+    Ascii,
+    /// The United Kingdom national replacement character set: `#` is replaced with `£`.
+    Uk,
+    /// DEC Special Graphics, also known as "line drawing" characters.
+    DecLineDrawing,
+    /// DEC Supplemental Graphics.
+    DecSupplemental,
+    /// DEC Technical characters.
+    DecTechnical,
+}
+
+impl Display for CharacterSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let designator = match self {
+            Self::Ascii => "B",
+            Self::Uk => "A",
+            Self::DecLineDrawing => "0",
+            Self::DecSupplemental => "%5",
+            Self::DecTechnical => ">",
+        };
+        f.write_str(designator)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        assert_eq!(Esc::SaveCursor.to_string(), "\x1b7");
+        assert_eq!(Esc::RestoreCursor.to_string(), "\x1b8");
+        assert_eq!(Esc::ApplicationKeypad.to_string(), "\x1b=");
+        assert_eq!(Esc::NumericKeypad.to_string(), "\x1b>");
+        assert_eq!(Esc::FullReset.to_string(), "\x1bc");
+        assert_eq!(Esc::ReverseIndex.to_string(), "\x1bM");
+        assert_eq!(Esc::ScreenAlignmentDisplay.to_string(), "\x1b#8");
+        assert_eq!(Esc::DoubleWidthLine.to_string(), "\x1b#6");
+        assert_eq!(
+            Esc::DesignateCharacterSet {
+                slot: CharacterSetSlot::G0,
+                charset: CharacterSet::Ascii,
+            }
+            .to_string(),
+            "\x1b(B"
+        );
+        assert_eq!(
+            Esc::DesignateCharacterSet {
+                slot: CharacterSetSlot::G1,
+                charset: CharacterSet::DecLineDrawing,
+            }
+            .to_string(),
+            "\x1b)0"
+        );
+        assert_eq!(
+            Esc::DesignateCharacterSet {
+                slot: CharacterSetSlot::G2,
+                charset: CharacterSet::DecSupplemental,
+            }
+            .to_string(),
+            "\x1b*%5"
+        );
+    }
+}
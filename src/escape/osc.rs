@@ -4,8 +4,9 @@
 
 use std::fmt::{self, Display};
 
-use crate::base64;
+use crate::{base64, style::RgbColor};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Osc<'a> {
     SetIconNameAndWindowTitle(&'a str),
     SetWindowTitle(&'a str),
@@ -15,6 +16,14 @@ pub enum Osc<'a> {
     ClearSelection(Selection),
     QuerySelection(Selection),
     SetSelection(Selection, &'a str),
+    /// The terminal replies with `Self::ReportForegroundColor`.
+    QueryForegroundColor,
+    /// The terminal replies with `Self::ReportBackgroundColor`.
+    QueryBackgroundColor,
+    /// The terminal's default foreground color.
+    ReportForegroundColor(RgbColor),
+    /// The terminal's default background color.
+    ReportBackgroundColor(RgbColor),
     // TODO: I didn't copy many available commands yet...
 }
 
@@ -33,12 +42,30 @@ impl Display for Osc<'_> {
                 // TODO: it'd be nice to avoid allocating a string to base64 encode.
                 write!(f, "52;{selection};{}", base64::encode(content.as_bytes()))?
             }
+            Self::QueryForegroundColor => write!(f, "10;?")?,
+            Self::QueryBackgroundColor => write!(f, "11;?")?,
+            Self::ReportForegroundColor(color) => write!(f, "10;{}", XColor(*color))?,
+            Self::ReportBackgroundColor(color) => write!(f, "11;{}", XColor(*color))?,
         }
         f.write_str(super::ST)?;
         Ok(())
     }
 }
 
+/// Displays a color in the `rgb:RRRR/GGGG/BBBB` form used by xterm in color reports.
+struct XColor(RgbColor);
+
+impl Display for XColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let RgbColor { red, green, blue } = self.0;
+        // Scale each channel to 16 bits by repeating it: 0xab becomes 0xabab.
+        write!(
+            f,
+            "rgb:{red:02x}{red:02x}/{green:02x}{green:02x}/{blue:02x}{blue:02x}"
+        )
+    }
+}
+
 bitflags::bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct Selection : u16 {
@@ -0,0 +1,263 @@
+// Terminal multiplexers like tmux and GNU screen parse the escape sequences an application writes
+// and redraw their own screen on the outer terminal. Sequences which the multiplexer doesn't
+// understand, like Kitty graphics or OSC 52 in some configurations, are dropped. Both
+// multiplexers offer a DCS "passthrough" which forwards its contents to the outer terminal as-is.
+//
+// tmux: <https://github.com/tmux/tmux/wiki/FAQ#what-is-the-passthrough-escape-sequence-and-how-do-i-use-it>
+// tmux requires `set -g allow-passthrough on` (tmux 3.3+) for the passthrough to be forwarded.
+//
+// GNU screen has no special prefix: the contents of any DCS are forwarded. Screen limits the
+// length of a string sequence however and ends the DCS on the first ST, so the payload has to be
+// split into multiple DCS chunks. An ST within the payload is split across two chunks between the
+// ESC and the backslash: screen keeps an ESC at the end of a string so the outer terminal sees
+// the ESC and backslash next to each other once both chunks have been forwarded.
+
+use std::{
+    fmt::{self, Display, Write as _},
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::style::Stylized;
+
+use super::{apc::Apc, csi::Csi, dcs::Dcs, esc::Esc, osc::Osc};
+
+/// The maximum number of bytes of payload written within one GNU screen DCS chunk.
+///
+/// Screen's string buffer (`MAXSTR`) is 768 bytes. Leave a little room for the ESC which may be
+/// added to the end of a chunk.
+const SCREEN_CHUNK_SIZE: usize = 760;
+
+/// A terminal multiplexer which requires passthrough wrapping for some escape sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplexer {
+    Tmux = 1,
+    Screen = 2,
+}
+
+static INITIALIZER: parking_lot::Once = parking_lot::Once::new();
+// 0 means no multiplexer, otherwise see the discriminants of `Multiplexer`.
+static DETECTED: AtomicU8 = AtomicU8::new(0);
+
+impl Multiplexer {
+    /// Detects whether the application is running within a terminal multiplexer.
+    ///
+    /// The `TMUX` and `STY` environment variables are checked first: these are set by tmux and
+    /// GNU screen respectively. These variables are not passed through SSH however so the `TERM`
+    /// is checked next: `tmux*` is considered tmux and `screen*` is considered GNU screen. Note
+    /// that older versions of tmux default to a `screen` `TERM`.
+    ///
+    /// The result is detected once and cached. Use [Self::force] to override it.
+    pub fn detect() -> Option<Self> {
+        INITIALIZER.call_once(|| {
+            let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+            let term = var("TERM").unwrap_or_default();
+            let detected = if var("TMUX").is_some() {
+                Some(Self::Tmux)
+            } else if var("STY").is_some() {
+                Some(Self::Screen)
+            } else if term.starts_with("tmux") {
+                Some(Self::Tmux)
+            } else if term.starts_with("screen") {
+                Some(Self::Screen)
+            } else {
+                None
+            };
+            DETECTED.store(detected.map_or(0, |m| m as u8), Ordering::SeqCst);
+        });
+        match DETECTED.load(Ordering::SeqCst) {
+            1 => Some(Self::Tmux),
+            2 => Some(Self::Screen),
+            _ => None,
+        }
+    }
+
+    /// Overrides detection of the multiplexer.
+    ///
+    /// Pass `None` to disable passthrough wrapping for escapes created with [Passthrough::detect]
+    /// and [PassthroughExt::passthrough].
+    pub fn force(multiplexer: Option<Self>) {
+        // Run the `Once` first so this override is not later overwritten by the `Once` fn.
+        let _ = Self::detect();
+        DETECTED.store(multiplexer.map_or(0, |m| m as u8), Ordering::SeqCst);
+    }
+}
+
+/// Wraps an escape sequence in the DCS passthrough for the given multiplexer.
+///
+/// When the multiplexer is `None` the escape is displayed as-is.
+///
+/// ```
+/// # use termina::escape::{osc::{Osc, Selection}, passthrough::{Multiplexer, Passthrough}};
+/// let osc = Osc::SetSelection(Selection::CLIPBOARD, "hi");
+/// assert_eq!(
+///     Passthrough::new(Some(Multiplexer::Tmux), osc).to_string(),
+///     "\x1bPtmux;\x1b\x1b]52;c;aGk=\x1b\x1b\\\x1b\\",
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Passthrough<T> {
+    pub multiplexer: Option<Multiplexer>,
+    pub escape: T,
+}
+
+impl<T: Display> Passthrough<T> {
+    pub const fn new(multiplexer: Option<Multiplexer>, escape: T) -> Self {
+        Self {
+            multiplexer,
+            escape,
+        }
+    }
+
+    /// Wraps the escape for the multiplexer found by [Multiplexer::detect], if any.
+    pub fn detect(escape: T) -> Self {
+        Self::new(Multiplexer::detect(), escape)
+    }
+}
+
+impl<T: Display> Display for Passthrough<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.multiplexer {
+            None => write!(f, "{}", self.escape),
+            Some(Multiplexer::Tmux) => {
+                write!(f, "{}tmux;", super::DCS)?;
+                write!(TmuxWriter { f }, "{}", self.escape)?;
+                f.write_str(super::ST)
+            }
+            Some(Multiplexer::Screen) => {
+                f.write_str(super::DCS)?;
+                write!(
+                    ScreenWriter {
+                        f,
+                        written: 0,
+                        pending_esc: false,
+                    },
+                    "{}",
+                    self.escape
+                )?;
+                f.write_str(super::ST)
+            }
+        }
+    }
+}
+
+/// Doubles every ESC in the payload as tmux requires.
+struct TmuxWriter<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+}
+
+impl fmt::Write for TmuxWriter<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (i, part) in s.split('\x1b').enumerate() {
+            if i != 0 {
+                self.f.write_str("\x1b\x1b")?;
+            }
+            self.f.write_str(part)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits the payload into DCS chunks which GNU screen will forward.
+struct ScreenWriter<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+    /// The number of payload bytes written in the current chunk.
+    written: usize,
+    /// Whether the last character written was an ESC.
+    pending_esc: bool,
+}
+
+impl ScreenWriter<'_, '_> {
+    fn next_chunk(&mut self) -> fmt::Result {
+        self.written = 0;
+        write!(self.f, "{}{}", super::ST, super::DCS)
+    }
+}
+
+impl fmt::Write for ScreenWriter<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for ch in s.chars() {
+            if (self.pending_esc && ch == '\\') || self.written + ch.len_utf8() > SCREEN_CHUNK_SIZE
+            {
+                self.next_chunk()?;
+            }
+            self.f.write_char(ch)?;
+            self.written += ch.len_utf8();
+            self.pending_esc = ch == '\x1b';
+        }
+        Ok(())
+    }
+}
+
+/// A helper trait to wrap escapes in [Passthrough] for the detected multiplexer.
+///
+/// ```
+/// # use termina::escape::{osc::Osc, passthrough::{Multiplexer, PassthroughExt as _}};
+/// Multiplexer::force(None);
+/// let title = Osc::SetWindowTitle("hello");
+/// assert_eq!(title.passthrough().to_string(), "\x1b]2;hello\x1b\\");
+/// ```
+pub trait PassthroughExt: Display + Sized {
+    fn passthrough(self) -> Passthrough<Self> {
+        Passthrough::detect(self)
+    }
+}
+
+impl PassthroughExt for Apc {}
+impl PassthroughExt for Csi {}
+impl PassthroughExt for Dcs {}
+impl PassthroughExt for Esc {}
+impl PassthroughExt for Osc<'_> {}
+/// Note that content within a passthrough is not seen by the multiplexer, so it isn't drawn on
+/// the multiplexer's own screen. This is mostly useful for content printed once, like the output
+/// of a CLI.
+impl PassthroughExt for Stylized<'_> {}
+
+#[cfg(test)]
+mod test {
+    use crate::escape::{csi, osc::Selection};
+
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        let set_selection = Osc::SetSelection(Selection::CLIPBOARD, "hello");
+        assert_eq!(
+            Passthrough::new(None, &set_selection).to_string(),
+            set_selection.to_string()
+        );
+        // Each ESC is doubled for tmux, including the ST of the OSC.
+        assert_eq!(
+            Passthrough::new(Some(Multiplexer::Tmux), &set_selection).to_string(),
+            "\x1bPtmux;\x1b\x1b]52;c;aGVsbG8=\x1b\x1b\\\x1b\\"
+        );
+        // For screen the OSC's ST is split across two chunks.
+        assert_eq!(
+            Passthrough::new(Some(Multiplexer::Screen), &set_selection).to_string(),
+            "\x1bP\x1b]52;c;aGVsbG8=\x1b\x1b\\\x1bP\\\x1b\\"
+        );
+        assert_eq!(
+            Passthrough::new(
+                Some(Multiplexer::Tmux),
+                Csi::Keyboard(csi::Keyboard::QueryFlags)
+            )
+            .to_string(),
+            "\x1bPtmux;\x1b\x1b[?u\x1b\\"
+        );
+    }
+
+    #[test]
+    fn screen_chunking() {
+        let content = "a".repeat(SCREEN_CHUNK_SIZE * 2 + 1);
+        let wrapped = Passthrough::new(Some(Multiplexer::Screen), &content).to_string();
+        let chunks: Vec<_> = wrapped
+            .strip_prefix(super::super::DCS)
+            .unwrap()
+            .strip_suffix(super::super::ST)
+            .unwrap()
+            .split("\x1b\\\x1bP")
+            .collect();
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= SCREEN_CHUNK_SIZE));
+        assert_eq!(chunks.concat(), content);
+    }
+}
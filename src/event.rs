@@ -2,8 +2,10 @@
 // sequences like CSI and DCS in the `Event` struct and do not make a distinction between
 // `InternalEvent` and `Event`. Otherwise all `KeyEvent` code is nearly identical to crossterm.
 
+use std::{any::Any, fmt, sync::Arc};
+
 use crate::{
-    escape::{csi::Csi, dcs::Dcs},
+    escape::{apc::Apc, csi::Csi, dcs::Dcs, osc::Osc},
     WindowSize,
 };
 
@@ -27,18 +29,106 @@ pub enum Event {
     /// paste"](https://en.wikipedia.org/wiki/Bracketed-paste) now however, which uses an escape
     /// sequence to deliver the entire pasted content.
     Paste(String),
+    /// The process was continued after being stopped, for example by `Terminal::suspend`.
+    ///
+    /// The terminal's contents and modes may have been changed while the process was stopped so
+    /// applications should redraw.
+    Resumed,
+    /// The terminal's input was closed, for example because the terminal window was closed.
+    ///
+    /// This is the last event read from the terminal: any further reads return an
+    /// `UnexpectedEof` error. Only emitted on Unix and by `VirtualTerminal`.
+    Closed,
+    /// A signal was received. See `UnixTerminal::watch_signals`.
+    Signal(Signal),
+    /// A file descriptor registered with `EventReader::register_fd` is readable. Only emitted
+    /// on Unix.
+    Readable(Token),
+    /// A timer added with `EventReader::add_timer` expired.
+    Timer(Token),
+    /// An event sent by the application with a `UserEventSender`.
+    User(UserEvent),
     /// A parsed escape sequence starting with CSI (control sequence introducer).
     Csi(Csi),
     Dcs(Dcs),
+    /// A parsed OSC (operating system command) escape sequence.
+    ///
+    /// Only replies to queries are parsed, so these never borrow from the input.
+    Osc(Osc<'static>),
+    Apc(Apc),
 }
 
 impl Event {
     #[inline]
     pub fn is_escape(&self) -> bool {
-        matches!(self, Self::Csi(_) | Self::Dcs(_))
+        matches!(
+            self,
+            Self::Csi(_) | Self::Dcs(_) | Self::Osc(_) | Self::Apc(_)
+        )
+    }
+}
+
+/// A process signal which can be delivered as an `Event::Signal`.
+///
+/// Signals are only delivered on Unix. See `UnixTerminal::watch_signals`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Signal {
+    /// SIGTERM: a polite request to terminate.
+    Terminate,
+    /// SIGINT: the user pressed Ctrl+C while the terminal was in cooked mode, or the signal was
+    /// sent with `kill`. In raw mode Ctrl+C is read as a key event instead.
+    Interrupt,
+    /// SIGHUP: the terminal was closed, for example because an SSH connection dropped.
+    Hangup,
+    /// SIGQUIT: the user pressed Ctrl+\\ while the terminal was in cooked mode.
+    Quit,
+    /// SIGUSR1
+    User1,
+    /// SIGUSR2
+    User2,
+}
+
+/// A payload sent to an `EventReader` with a `UserEventSender`.
+///
+/// The payload can be any type and is retrieved with [Self::downcast_ref]. Clones share the
+/// payload and two user events are equal if they share the same payload.
+#[derive(Clone)]
+pub struct UserEvent(Arc<dyn Any + Send + Sync>);
+
+impl UserEvent {
+    pub fn new<T: Any + Send + Sync>(payload: T) -> Self {
+        Self(Arc::new(payload))
+    }
+
+    /// Returns the payload if it is a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.0.is::<T>()
+    }
+}
+
+impl fmt::Debug for UserEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("UserEvent(..)")
     }
 }
 
+impl PartialEq for UserEvent {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for UserEvent {}
+
+/// An identifier chosen by the application for a file descriptor or timer registered with an
+/// `EventReader`. It is passed back in `Event::Readable` and `Event::Timer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Token(pub usize);
+
 // CREDIT: <https://github.com/crossterm-rs/crossterm/blob/36d95b26a26e64b0f8c12edfe11f410a6d56a812/src/event.rs#L777-L1158>
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct KeyEvent {
@@ -4,15 +4,112 @@
 // statically.
 // Instead of crossterm's `Filter` trait I have opted for a `Fn(&Event) -> bool` for simplicity.
 
-use std::{collections::VecDeque, io, sync::Arc, time::Duration};
+#[cfg(unix)]
+use std::os::fd::OwnedFd;
+use std::{
+    any::Any,
+    collections::VecDeque,
+    io,
+    sync::{Arc, Weak},
+    task,
+    time::{Duration, Instant},
+};
 
-use parking_lot::Mutex;
+use parking_lot::{Mutex, MutexGuard};
 
 use super::{
-    source::{EventSource as _, PlatformEventSource, PlatformWaker, PollTimeout},
-    Event,
+    source::{EventSource, PollTimeout, Waker},
+    Event, MouseEventKind, Token, UserEvent,
 };
 
+/// Which consecutive events an [EventReader] merges into one. See
+/// [EventReader::set_coalescing].
+///
+/// Coalescing only merges an event with the events directly after it, so the order of events is
+/// kept: a resize, a key press and another resize are read as three events. The default
+/// coalesces nothing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Coalescing {
+    /// Merge consecutive `Event::WindowResized` events into the last one.
+    pub resizes: bool,
+    /// Merge consecutive mouse move and drag events with the same button and modifiers into the
+    /// last one.
+    pub mouse_moves: bool,
+    /// How long to wait for a following event after reading an event which can be coalesced.
+    ///
+    /// With a zero window only events which are already available are merged. A longer window
+    /// debounces the events, for example while a window edge is dragged, at the cost of
+    /// delaying each coalesced event by up to the window. The window is cut short by the timeout
+    /// passed to `poll`, so polling with a zero timeout never waits.
+    pub window: Duration,
+}
+
+impl Coalescing {
+    fn applies_to(&self, event: &Event) -> bool {
+        match event {
+            Event::WindowResized(_) => self.resizes,
+            Event::Mouse(mouse) => {
+                self.mouse_moves
+                    && matches!(mouse.kind, MouseEventKind::Moved | MouseEventKind::Drag(_))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether `next` replaces `event`.
+    fn merges(&self, event: &Event, next: &Event) -> bool {
+        match (event, next) {
+            (Event::WindowResized(_), Event::WindowResized(_)) => self.resizes,
+            (Event::Mouse(mouse), Event::Mouse(next)) => {
+                self.applies_to(event)
+                    && mouse.kind == next.kind
+                    && mouse.modifiers == next.modifiers
+            }
+            _ => false,
+        }
+    }
+}
+
+/// How a [Subscription] is set up. See [EventReader::subscribe].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriptionOptions {
+    /// The number of events the subscription buffers before it lags. The default is 256.
+    ///
+    /// Events which the subscription reads from the terminal on behalf of the [EventReader] are
+    /// queued for the reader with the same capacity and [Self::lagged] policy, so a reader which
+    /// is never read from doesn't buffer events without bound.
+    pub capacity: usize,
+    /// What happens to events which arrive while the buffer is full.
+    pub lagged: Lagged,
+    /// Whether the events accepted by the subscription are only delivered to it. Events taken
+    /// by an exclusive subscription are not read by the [EventReader] or by other
+    /// subscriptions, which is useful for consuming replies to queries privately.
+    ///
+    /// Exclusive subscriptions should not accept `Event::Readable`: the FD is only polled again
+    /// once the event is read from the reader itself.
+    pub exclusive: bool,
+}
+
+impl Default for SubscriptionOptions {
+    fn default() -> Self {
+        Self {
+            capacity: 256,
+            lagged: Lagged::DropOldest,
+            exclusive: false,
+        }
+    }
+}
+
+/// What a [Subscription] with a full buffer does with new events. Either way the number of
+/// dropped events is counted by [Subscription::take_lagged].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lagged {
+    /// Drop the oldest buffered event to make room, so the subscription sees the latest events.
+    DropOldest,
+    /// Drop the new event, so the subscription sees the events directly after its last read.
+    DropNewest,
+}
+
 /// A reader of events from the terminal's input handle.
 ///
 /// Note that this type wraps an `Arc` and is cheap to clone. If the `event-stream` feature is
@@ -20,113 +117,1038 @@ use super::{
 #[derive(Debug, Clone)]
 pub struct EventReader {
     shared: Arc<Mutex<Shared>>,
+    /// These are kept outside of `shared` so that they can be used while another thread is
+    /// blocked reading.
+    events: Arc<Mutex<VecDeque<Event>>>,
+    user_events: Arc<Mutex<VecDeque<Event>>>,
+    /// The tokens of the `Event::Readable` events which were read while another thread held
+    /// `shared`. Their FDs are re-armed once `shared` is available.
+    #[cfg(unix)]
+    rearm: Arc<Mutex<Vec<Token>>>,
+    /// These are kept outside of `shared` so that subscribing doesn't wait for a blocked read.
+    subscribers: Arc<Mutex<Vec<Weak<Mutex<Subscriber>>>>>,
+    streams: Arc<Mutex<StreamWakers>>,
+    fences: Arc<Mutex<Fences>>,
+    waker: Waker,
+    /// Whether the source can be polled through FDs, see `EventSource::fds`.
+    #[cfg(all(unix, any(feature = "tokio", feature = "async-io")))]
+    has_fds: bool,
+}
+
+/// The wakers of the `EventStream`s which read from an [EventReader] without a helper thread.
+///
+/// Such a stream only waits for the source's FDs to become readable, so it is woken through here
+/// when another caller queues an event for it or changes the timers or FDs it waits on, and when
+/// it found the reader locked.
+#[derive(Debug, Default)]
+struct StreamWakers {
+    wakers: Vec<Weak<Mutex<Option<task::Waker>>>>,
+    /// Whether a stream found the reader locked since the wakers were last woken.
+    contended: bool,
+}
+
+/// Counts the DA1 requests which end query batches and the replies to them. See
+/// `terminal::query`.
+///
+/// DA1 replies can't be told apart so a batch ends with the reply which matches its request by
+/// count: the replies to earlier batches which timed out may still arrive first.
+#[derive(Debug, Default)]
+struct Fences {
+    requested: u64,
+    received: u64,
+}
+
+/// What [EventReader::poll_inline] found.
+#[cfg(all(unix, any(feature = "tokio", feature = "async-io")))]
+pub(crate) enum Inline {
+    /// An event accepted by the filter, taken from the queue.
+    Event(Event),
+    /// No event is available. The stream should wait until the source's FDs are readable or the
+    /// earliest timer expires. The FDs are returned if they changed.
+    NotReady {
+        timer: Option<Instant>,
+        fds: Option<(u64, Vec<OwnedFd>)>,
+    },
+    /// Another caller holds the reader. The stream is woken once it is released.
+    Locked,
 }
 
 impl EventReader {
-    pub(crate) fn new(source: PlatformEventSource) -> Self {
+    pub(crate) fn new(source: impl EventSource + 'static) -> Self {
+        let events = Arc::new(Mutex::new(VecDeque::with_capacity(32)));
+        let user_events = Arc::new(Mutex::new(VecDeque::new()));
+        #[cfg(unix)]
+        let rearm = Arc::new(Mutex::new(Vec::new()));
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+        let streams = Arc::new(Mutex::new(StreamWakers::default()));
+        let fences = Arc::new(Mutex::new(Fences::default()));
+        let waker = source.waker();
+        #[cfg(all(unix, any(feature = "tokio", feature = "async-io")))]
+        let has_fds = source.fds().is_some();
         let shared = Shared {
-            events: VecDeque::with_capacity(32),
-            source,
+            events: events.clone(),
+            user_events: user_events.clone(),
+            #[cfg(unix)]
+            rearm: rearm.clone(),
+            subscribers: subscribers.clone(),
+            source: Box::new(source),
             skipped_events: Vec::with_capacity(32),
+            pending: VecDeque::new(),
+            coalescing: Coalescing::default(),
+            resizes: 0,
+            pixel_size: None,
+            timers: Vec::new(),
+            changed: false,
         };
         Self {
             shared: Arc::new(Mutex::new(shared)),
+            events,
+            user_events,
+            #[cfg(unix)]
+            rearm,
+            subscribers,
+            streams,
+            fences,
+            waker,
+            #[cfg(all(unix, any(feature = "tokio", feature = "async-io")))]
+            has_fds,
         }
     }
 
-    pub fn waker(&self) -> PlatformWaker {
-        let reader = self.shared.lock();
-        reader.source.waker()
+    /// Returns a handle which interrupts a blocked `poll` or `read`, making it return `Ok(false)`
+    /// as if it had timed out.
+    ///
+    /// Use a [UserEventSender] instead to wake the reader in a way that the reading thread can
+    /// tell apart from a timeout.
+    pub fn waker(&self) -> Waker {
+        self.waker.clone()
+    }
+
+    /// Returns a handle for sending `Event::User` events to this reader from any thread.
+    pub fn user_event_sender(&self) -> UserEventSender {
+        UserEventSender {
+            user_events: self.user_events.clone(),
+            waker: self.waker.clone(),
+        }
     }
 
     pub fn poll<F>(&self, timeout: Option<Duration>, filter: F) -> io::Result<bool>
     where
         F: FnMut(&Event) -> bool,
     {
-        let (mut reader, timeout) = if let Some(timeout) = timeout {
-            let poll_timeout = PollTimeout::new(Some(timeout));
-            if let Some(reader) = self.shared.try_lock_for(timeout) {
-                (reader, poll_timeout.leftover())
-            } else {
+        let timeout = PollTimeout::new(timeout);
+        let mut filter = filter;
+        if self.events.lock().iter().any(&mut filter) {
+            return Ok(true);
+        }
+        loop {
+            let Some(mut reader) = self.lock(&timeout) else {
                 return Ok(false);
+            };
+            let polled = reader.poll(timeout.leftover(), &mut filter);
+            let fair = matches!(polled, Ok(Polled::Yield));
+            self.unlock(reader, fair);
+            match polled? {
+                Polled::Ready => return Ok(true),
+                Polled::NotReady => return Ok(false),
+                Polled::Yield => (),
+            }
+        }
+    }
+
+    pub fn read<F>(&self, mut filter: F) -> io::Result<Event>
+    where
+        F: FnMut(&Event) -> bool,
+    {
+        loop {
+            if let Some(event) = self.take(&mut filter) {
+                return Ok(event);
             }
+            let _ = self.poll(None, &mut filter)?;
+        }
+    }
+
+    /// Removes the first queued event accepted by the filter. This doesn't wait for a thread
+    /// which is blocked reading.
+    pub(crate) fn take<F>(&self, filter: F) -> Option<Event>
+    where
+        F: FnMut(&Event) -> bool,
+    {
+        let event = {
+            let mut events = self.events.lock();
+            let i = events.iter().position(filter)?;
+            events.remove(i)?
+        };
+        #[cfg(unix)]
+        if let Event::Readable(token) = event {
+            self.rearm.lock().push(token);
+            match self.shared.try_lock() {
+                Some(mut reader) => {
+                    reader.rearm_fds();
+                    self.unlock(reader, false);
+                }
+                // Interrupt the thread which holds the lock so that it polls the FD again.
+                None => {
+                    let _ = self.waker.wake();
+                }
+            }
+        }
+        Some(event)
+    }
+
+    /// Locks the shared state, waiting at most until the timeout.
+    fn lock(&self, timeout: &PollTimeout) -> Option<MutexGuard<'_, Shared>> {
+        match timeout.leftover() {
+            Some(leftover) => self.shared.try_lock_for(leftover),
+            None => Some(self.shared.lock()),
+        }
+    }
+
+    /// Releases the lock on the shared state, waking the streams waiting on the reader if
+    /// anything they wait on changed or if one of them found the reader locked.
+    fn unlock(&self, mut reader: MutexGuard<'_, Shared>, fair: bool) {
+        let changed = std::mem::take(&mut reader.changed);
+        if fair {
+            MutexGuard::unlock_fair(reader);
         } else {
-            (self.shared.lock(), None)
+            drop(reader);
+        }
+
+        let wakers: Vec<_> = {
+            let mut streams = self.streams.lock();
+            if !std::mem::take(&mut streams.contended) && !changed {
+                return;
+            }
+            streams.wakers.retain(|waker| waker.strong_count() > 0);
+            streams
+                .wakers
+                .iter()
+                .filter_map(|waker| waker.upgrade()?.lock().take())
+                .collect()
         };
-        reader.poll(timeout, filter)
+        for waker in wakers {
+            waker.wake();
+        }
     }
 
-    pub fn read<F>(&self, filter: F) -> io::Result<Event>
+    /// Runs `f` with the shared state locked.
+    fn with_shared<R>(&self, f: impl FnOnce(&mut Shared) -> R) -> R {
+        let mut reader = self.shared.lock();
+        let result = f(&mut reader);
+        self.unlock(reader, false);
+        result
+    }
+
+    /// Registers the waker of an `EventStream` which calls [Self::poll_inline]. The stream
+    /// stores its task's waker in `waker` before each call and it is taken when the stream is
+    /// woken.
+    #[cfg(all(unix, any(feature = "tokio", feature = "async-io")))]
+    pub(crate) fn add_stream_waker(&self, waker: &Arc<Mutex<Option<task::Waker>>>) {
+        self.streams.lock().wakers.push(Arc::downgrade(waker));
+    }
+
+    /// Whether the source can be polled through FDs, so that [Self::poll_inline] can be used.
+    #[cfg(all(unix, any(feature = "tokio", feature = "async-io")))]
+    pub(crate) fn has_fds(&self) -> bool {
+        self.has_fds
+    }
+
+    /// Reads the events which are available without blocking, returning the first queued event
+    /// accepted by `filter`. This is used by an `EventStream` which waits for the source's FDs
+    /// with an async runtime's reactor.
+    ///
+    /// The FDs are returned if their generation differs from `generation`. Rather than waiting
+    /// for the lock this returns `Inline::Locked` if another caller holds the reader.
+    #[cfg(all(unix, any(feature = "tokio", feature = "async-io")))]
+    pub(crate) fn poll_inline<F>(
+        &self,
+        mut filter: F,
+        generation: Option<u64>,
+    ) -> io::Result<Inline>
     where
         F: FnMut(&Event) -> bool,
     {
-        let mut reader = self.shared.lock();
-        reader.read(filter)
+        loop {
+            if let Some(event) = self.take(&mut filter) {
+                return Ok(Inline::Event(event));
+            }
+            let mut reader = match self.shared.try_lock() {
+                Some(reader) => reader,
+                None => {
+                    self.streams.lock().contended = true;
+                    // The lock may have been released before `contended` was set, in which case
+                    // nothing would wake the stream.
+                    match self.shared.try_lock() {
+                        Some(reader) => reader,
+                        None => return Ok(Inline::Locked),
+                    }
+                }
+            };
+            let result: io::Result<Option<Inline>> = (|| loop {
+                match reader.poll(Some(Duration::ZERO), &mut filter)? {
+                    // The event is taken at the top of the loop.
+                    Polled::Ready => return Ok(None),
+                    // Read on until the source has no more events, as the reactor only wakes the
+                    // stream for new input.
+                    Polled::Yield => (),
+                    Polled::NotReady => {
+                        let timer = reader.timers.iter().map(|(deadline, _)| *deadline).min();
+                        let fds = reader.source_fds(generation).transpose()?;
+                        return Ok(Some(Inline::NotReady { timer, fds }));
+                    }
+                }
+            })();
+            self.unlock(reader, false);
+            if let Some(inline) = result? {
+                return Ok(inline);
+            }
+        }
+    }
+
+    /// Subscribes to the events accepted by `filter` which are read after this call.
+    ///
+    /// Each subscription has its own buffer, so a subscription sees every event its filter
+    /// accepts no matter which thread reads from the terminal or how the reader and other
+    /// subscriptions filter events. Unless the subscription is
+    /// [exclusive](SubscriptionOptions::exclusive) the events are also read by the reader as
+    /// usual. A subscription which isn't read from fast enough drops events according to
+    /// [SubscriptionOptions::lagged].
+    ///
+    /// ```
+    /// # use termina::{event::KeyCode, Event, SubscriptionOptions, Terminal as _};
+    /// # use termina::{VirtualTerminal, WindowSize};
+    /// # let size = WindowSize { cols: 80, rows: 24, pixel_width: None, pixel_height: None };
+    /// # let terminal = VirtualTerminal::new(size);
+    /// let is_key = |event: &Event| matches!(event, Event::Key(_));
+    /// let recorder = terminal
+    ///     .event_reader()
+    ///     .subscribe(SubscriptionOptions::default(), is_key);
+    /// terminal.input().send_bytes(b"q");
+    ///
+    /// // Both the terminal and the subscription see the key press.
+    /// let Event::Key(key) = terminal.read(is_key)? else { unreachable!() };
+    /// assert_eq!(key.code, KeyCode::Char('q'));
+    /// assert_eq!(recorder.try_read(), Some(Event::Key(key)));
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn subscribe<F>(&self, options: SubscriptionOptions, filter: F) -> Subscription
+    where
+        F: Fn(&Event) -> bool + Send + Sync + 'static,
+    {
+        let subscriber = Arc::new(Mutex::new(Subscriber {
+            filter: Box::new(filter),
+            options,
+            events: VecDeque::new(),
+            lagged: 0,
+        }));
+        self.subscribers.lock().push(Arc::downgrade(&subscriber));
+        Subscription {
+            reader: self.clone(),
+            subscriber,
+        }
+    }
+
+    /// Sets which events are coalesced. This applies to events which have not been read from
+    /// the terminal yet.
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use termina::{Coalescing, Terminal as _, VirtualTerminal, WindowSize};
+    /// # let size = WindowSize { cols: 80, rows: 24, pixel_width: None, pixel_height: None };
+    /// # let terminal = VirtualTerminal::new(size);
+    /// terminal.event_reader().set_coalescing(Coalescing {
+    ///     resizes: true,
+    ///     mouse_moves: true,
+    ///     window: Duration::from_millis(20),
+    /// });
+    /// ```
+    pub fn set_coalescing(&self, coalescing: Coalescing) {
+        self.with_shared(|reader| reader.coalescing = coalescing);
+    }
+
+    /// Polls `fd` along with the terminal, emitting an `Event::Readable` with the token when it
+    /// becomes readable. This is only supported on Unix and not by `VirtualTerminal`.
+    ///
+    /// This lets an application wait for input from the terminal and from other sources like
+    /// sockets or the controlling side of a PTY in a single call to `read`. The application
+    /// reads from its own handle to the FD: pass a duplicate, for example from
+    /// `AsFd::as_fd(&socket).try_clone_to_owned()`.
+    ///
+    /// The FD is polled for readability and reported once: after an `Event::Readable` it is not
+    /// polled again until that event has been returned by [Self::read]. The application should
+    /// read what is available from the FD when handling the event, otherwise the event is
+    /// emitted again right away.
+    ///
+    /// Registering blocks while another thread is reading or polling this reader. Returns an
+    /// `AlreadyExists` error if an FD is already registered with the token.
+    #[cfg(unix)]
+    pub fn register_fd(&self, fd: OwnedFd, token: Token) -> io::Result<()> {
+        self.with_shared(|reader| {
+            reader.changed = true;
+            reader.source.register_fd(fd, token)
+        })
+    }
+
+    /// Stops polling the FD registered with `token`, returning it.
+    #[cfg(unix)]
+    pub fn deregister_fd(&self, token: Token) -> Option<OwnedFd> {
+        self.with_shared(|reader| {
+            reader.changed = true;
+            reader.source.deregister_fd(token)
+        })
+    }
+
+    /// Emits an `Event::Timer` with the token once `deadline` has passed.
+    ///
+    /// Timers fire once. They are only checked while the reader is polling or reading, so the
+    /// event is emitted by the first `poll` or `read` at or after the deadline. Like
+    /// [Self::register_fd] adding a timer blocks while another thread is reading.
+    pub fn add_timer(&self, deadline: Instant, token: Token) {
+        self.with_shared(|reader| {
+            reader.timers.push((deadline, token));
+            reader.changed = true;
+        });
+    }
+
+    /// Removes the timers with the given token which have not fired yet, returning whether any
+    /// were removed.
+    pub fn cancel_timer(&self, token: Token) -> bool {
+        self.with_shared(|reader| {
+            let len = reader.timers.len();
+            reader.timers.retain(|(_, timer)| *timer != token);
+            reader.timers.len() != len
+        })
+    }
+
+    /// Records that a query batch wrote a DA1 request, returning the number of the DA1 reply
+    /// which ends the batch.
+    pub(crate) fn request_fence(&self) -> u64 {
+        let mut fences = self.fences.lock();
+        fences.requested += 1;
+        fences.requested
+    }
+
+    /// Records a DA1 reply read by a query batch, returning whether it is reply number `fence`
+    /// or a later one.
+    pub(crate) fn receive_fence(&self, fence: u64) -> bool {
+        let mut fences = self.fences.lock();
+        fences.received += 1;
+        fences.received >= fence
+    }
+
+    /// Returns the pixel size cached by `Terminal::query_dimensions` along with the number of
+    /// `Event::WindowResized` events read so far, which is passed back to
+    /// [Self::cache_pixel_size].
+    pub(crate) fn cached_pixel_size(&self) -> (u64, Option<(u16, u16)>) {
+        self.with_shared(|reader| (reader.resizes, reader.pixel_size))
+    }
+
+    /// Caches the pixel size of the text area until the next `Event::WindowResized`. Nothing is
+    /// cached if the window was resized since `resizes` was read, as the size may be outdated.
+    pub(crate) fn cache_pixel_size(&self, resizes: u64, pixel_size: (u16, u16)) {
+        self.with_shared(|reader| {
+            if reader.resizes == resizes {
+                reader.pixel_size = Some(pixel_size);
+            }
+        });
     }
 }
 
+/// Sends application events to an [EventReader]. See [EventReader::user_event_sender].
+///
+/// This is useful for waking the thread which handles terminal events, for example with the
+/// result of a background task. User events are read in the order they were sent and are mixed
+/// in with the terminal's events: they are returned by `poll` and `read` when their filters
+/// accept `Event::User` and are also emitted by an `EventStream`.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use termina::{event::UserEvent, Event, Terminal as _, VirtualTerminal, WindowSize};
+/// # let size = WindowSize { cols: 80, rows: 24, pixel_width: None, pixel_height: None };
+/// # let terminal = VirtualTerminal::new(size);
+/// let sender = terminal.event_reader().user_event_sender();
+/// std::thread::spawn(move || sender.send("done"));
+///
+/// let Event::User(event) = terminal.read(|event| matches!(event, Event::User(_)))? else {
+///     unreachable!()
+/// };
+/// assert_eq!(event.downcast_ref::<&str>(), Some(&"done"));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct UserEventSender {
+    user_events: Arc<Mutex<VecDeque<Event>>>,
+    waker: Waker,
+}
+
+impl UserEventSender {
+    /// Sends `payload` as an `Event::User`, waking the reader if it is blocked.
+    pub fn send<T: Any + Send + Sync>(&self, payload: T) -> io::Result<()> {
+        self.send_event(UserEvent::new(payload))
+    }
+
+    pub fn send_event(&self, event: UserEvent) -> io::Result<()> {
+        // Waking with the queue locked means that a reader which takes the event also sees the
+        // wake, see `Shared::read_source`.
+        let mut user_events = self.user_events.lock();
+        user_events.push_back(Event::User(event));
+        self.waker.wake()
+    }
+}
+
+/// An independent view of the events read by an [EventReader]. See [EventReader::subscribe].
+///
+/// Reading from a subscription reads from the terminal when the subscription's buffer is empty,
+/// so a subscription can be read on its own thread while other threads use the reader or other
+/// subscriptions. Dropping the subscription unsubscribes.
 #[derive(Debug)]
-struct Shared {
+pub struct Subscription {
+    reader: EventReader,
+    subscriber: Arc<Mutex<Subscriber>>,
+}
+
+impl Subscription {
+    /// Checks whether the subscription has an event, waiting at most `timeout` for one.
+    ///
+    /// Like [EventReader::poll] this returns `Ok(false)` early when the reader is woken.
+    pub fn poll(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let timeout = PollTimeout::new(timeout);
+        loop {
+            if !self.subscriber.lock().events.is_empty() {
+                return Ok(true);
+            }
+            let Some(mut reader) = self.reader.lock(&timeout) else {
+                return Ok(false);
+            };
+            // Another thread may have read an event for this subscription while this one waited
+            // for the lock.
+            if !self.subscriber.lock().events.is_empty() {
+                return Ok(true);
+            }
+            let options = self.subscriber.lock().options;
+            let polled = reader.poll_subscription(timeout.leftover(), &options);
+            let fair = matches!(polled, Ok(Polled::Ready | Polled::Yield));
+            self.reader.unlock(reader, fair);
+            match polled {
+                Ok(Polled::Ready | Polled::Yield) => (),
+                Ok(Polled::NotReady) if timeout.elapsed() => return Ok(false),
+                Ok(Polled::NotReady) => (),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => return Ok(false),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Reads the next event, blocking until there is one.
+    pub fn read(&self) -> io::Result<Event> {
+        loop {
+            if let Some(event) = self.try_read() {
+                return Ok(event);
+            }
+            let _ = self.poll(None)?;
+        }
+    }
+
+    /// Reads the next buffered event without reading from the terminal.
+    pub fn try_read(&self) -> Option<Event> {
+        self.subscriber.lock().events.pop_front()
+    }
+
+    /// Returns the number of events dropped because the buffer was full since the last call.
+    pub fn take_lagged(&self) -> u64 {
+        std::mem::take(&mut self.subscriber.lock().lagged)
+    }
+}
+
+struct Subscriber {
+    filter: Box<dyn Fn(&Event) -> bool + Send + Sync>,
+    options: SubscriptionOptions,
     events: VecDeque<Event>,
-    source: PlatformEventSource,
+    lagged: u64,
+}
+
+impl Subscriber {
+    fn push(&mut self, event: Event) {
+        if push_bounded(&mut self.events, event, &self.options).is_some() {
+            self.lagged += 1;
+        }
+    }
+}
+
+/// Queues the event unless the queue holds `options.capacity` events already, in which case an
+/// event is dropped according to `options.lagged`. Returns the dropped event.
+fn push_bounded(
+    events: &mut VecDeque<Event>,
+    event: Event,
+    options: &SubscriptionOptions,
+) -> Option<Event> {
+    if events.len() < options.capacity.max(1) {
+        events.push_back(event);
+        return None;
+    }
+    match options.lagged {
+        Lagged::DropOldest => {
+            let dropped = events.pop_front();
+            events.push_back(event);
+            dropped
+        }
+        Lagged::DropNewest => Some(event),
+    }
+}
+
+impl std::fmt::Debug for Subscriber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subscriber")
+            .field("options", &self.options)
+            .field("events", &self.events)
+            .field("lagged", &self.lagged)
+            .finish()
+    }
+}
+
+/// The result of polling while holding the lock on [Shared].
+enum Polled {
+    Ready,
+    NotReady,
+    /// An event was delivered to a subscription or queued for another filter. The lock should be
+    /// released fairly so that a thread waiting to poll that subscription or filter can notice
+    /// it.
+    Yield,
+}
+
+#[derive(Debug)]
+struct Shared {
+    events: Arc<Mutex<VecDeque<Event>>>,
+    /// Events sent with a `UserEventSender`. These are read before the source.
+    user_events: Arc<Mutex<VecDeque<Event>>>,
+    #[cfg(unix)]
+    rearm: Arc<Mutex<Vec<Token>>>,
+    subscribers: Arc<Mutex<Vec<Weak<Mutex<Subscriber>>>>>,
+    source: Box<dyn EventSource>,
     skipped_events: Vec<Event>,
+    /// Events read from the source while coalescing which didn't merge with the coalesced event.
+    /// These are read before the source.
+    pending: VecDeque<Event>,
+    coalescing: Coalescing,
+    /// The number of `Event::WindowResized` events read from the source.
+    resizes: u64,
+    /// The width and height of the text area in pixels. See `Terminal::query_dimensions`.
+    pixel_size: Option<(u16, u16)>,
+    /// Timers added with `EventReader::add_timer`.
+    timers: Vec<(Instant, Token)>,
+    /// Whether events were queued or the timers or FDs changed since the lock was taken. See
+    /// `EventReader::unlock`.
+    changed: bool,
 }
 
 impl Shared {
-    fn poll<F>(&mut self, timeout: Option<Duration>, mut filter: F) -> io::Result<bool>
+    fn poll<F>(&mut self, timeout: Option<Duration>, mut filter: F) -> io::Result<Polled>
     where
         F: FnMut(&Event) -> bool,
     {
-        if self.events.iter().any(&mut (filter)) {
-            return Ok(true);
+        if self.events.lock().iter().any(&mut (filter)) {
+            return Ok(Polled::Ready);
         }
 
         let timeout = PollTimeout::new(timeout);
 
         loop {
-            let maybe_event = match self.source.try_read(timeout.leftover()) {
-                Ok(None) => None,
-                Ok(Some(event)) => {
-                    if (filter)(&event) {
-                        Some(event)
-                    } else {
+            let (maybe_event, delivered) = match self.read_event(timeout.leftover()) {
+                Ok(None) => (None, false),
+                Ok(Some(event)) => match self.dispatch(event) {
+                    (Some(event), delivered) if (filter)(&event) => (Some(event), delivered),
+                    (Some(event), delivered) => {
                         self.skipped_events.push(event);
-                        None
+                        (None, delivered)
                     }
+                    (None, delivered) => (None, delivered),
+                },
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                    return Ok(Polled::NotReady)
                 }
-                Err(err) if err.kind() == io::ErrorKind::Interrupted => return Ok(false),
                 Err(err) => return Err(err),
             };
 
-            if timeout.elapsed() || maybe_event.is_some() {
-                self.events.extend(self.skipped_events.drain(..));
+            let skipped = !self.skipped_events.is_empty();
+            if timeout.elapsed() || maybe_event.is_some() || delivered || skipped {
+                self.changed |= skipped;
+                let mut events = self.events.lock();
+                events.extend(self.skipped_events.drain(..));
 
                 if let Some(event) = maybe_event {
-                    self.events.push_front(event);
-                    return Ok(true);
+                    events.push_front(event);
+                    return Ok(Polled::Ready);
                 }
 
-                return Ok(false);
+                if delivered || skipped {
+                    return Ok(Polled::Yield);
+                }
+                return Ok(Polled::NotReady);
             }
         }
     }
 
-    fn read<F>(&mut self, mut filter: F) -> io::Result<Event>
-    where
-        F: FnMut(&Event) -> bool,
-    {
-        let mut skipped_events = VecDeque::new();
+    /// Reads one event for a [Subscription], queueing it for the reader and the subscriptions.
+    ///
+    /// The reader's queue is bounded by the subscription's options, so that it doesn't grow
+    /// without bound if only subscriptions are read.
+    fn poll_subscription(
+        &mut self,
+        timeout: Option<Duration>,
+        options: &SubscriptionOptions,
+    ) -> io::Result<Polled> {
+        match self.read_event(timeout) {
+            Ok(Some(event)) => {
+                if let (Some(event), _) = self.dispatch(event) {
+                    #[cfg_attr(not(unix), allow(unused_variables))]
+                    let dropped = push_bounded(&mut self.events.lock(), event, options);
+                    // The FD is only polled again once its event is read, so re-arm it when
+                    // the event is dropped.
+                    #[cfg(unix)]
+                    if let Some(Event::Readable(token)) = dropped {
+                        self.source.rearm_fd(token);
+                    }
+                    self.changed = true;
+                }
+                Ok(Polled::Yield)
+            }
+            Ok(None) => Ok(Polled::NotReady),
+            Err(err) => Err(err),
+        }
+    }
 
+    /// Delivers the event to the subscriptions which accept it. If an exclusive subscription
+    /// accepts the event only the first of those receives it, otherwise the event is returned
+    /// for the reader. Also returns whether any subscription received the event.
+    fn dispatch(&mut self, event: Event) -> (Option<Event>, bool) {
+        let mut subscribers = self.subscribers.lock();
+        if subscribers.is_empty() {
+            return (Some(event), false);
+        }
+        let mut accepting: Vec<_> = Vec::new();
+        subscribers.retain(|subscriber| match subscriber.upgrade() {
+            Some(subscriber) => {
+                if (subscriber.lock().filter)(&event) {
+                    accepting.push(subscriber);
+                }
+                true
+            }
+            None => false,
+        });
+        drop(subscribers);
+
+        if let Some(exclusive) = accepting
+            .iter()
+            .find(|subscriber| subscriber.lock().options.exclusive)
+        {
+            exclusive.lock().push(event);
+            return (None, true);
+        }
+        let delivered = !accepting.is_empty();
+        for subscriber in accepting {
+            subscriber.lock().push(event.clone());
+        }
+        (Some(event), delivered)
+    }
+
+    /// Reads the next event, merging it with the events after it according to
+    /// `self.coalescing`.
+    fn read_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<Event>> {
+        let timeout = PollTimeout::new(timeout);
+        let mut event = match self.pending.pop_front() {
+            Some(event) => event,
+            None => match self.read_source(timeout.leftover())? {
+                Some(event) => event,
+                None => return Ok(None),
+            },
+        };
+        if !self.coalescing.applies_to(&event) {
+            return Ok(Some(event));
+        }
+
+        // The window never extends the caller's timeout, so a zero timeout only merges the
+        // events which are already available.
+        let window = match timeout.leftover() {
+            Some(leftover) => leftover.min(self.coalescing.window),
+            None => self.coalescing.window,
+        };
+        let window = PollTimeout::new(Some(window));
         loop {
-            while let Some(event) = self.events.pop_front() {
-                if (filter)(&event) {
-                    self.events.extend(skipped_events.drain(..));
-                    return Ok(event);
-                } else {
-                    skipped_events.push_back(event);
+            match self.read_source(window.leftover()) {
+                Ok(Some(next)) if self.coalescing.merges(&event, &next) => event = next,
+                Ok(Some(next)) => {
+                    self.pending.push_back(next);
+                    break;
+                }
+                Ok(None) => break,
+                // Return the event now and the wake on the next read.
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                    self.source.waker().wake()?;
+                    break;
+                }
+                Err(err) => {
+                    self.pending.push_back(event);
+                    return Err(err);
                 }
             }
-            let _ = self.poll(None, &mut filter)?;
         }
+        Ok(Some(event))
+    }
+
+    /// Reads from the source, returning early if a timer expires.
+    fn read_source(&mut self, timeout: Option<Duration>) -> io::Result<Option<Event>> {
+        #[cfg(unix)]
+        self.rearm_fds();
+        {
+            let mut user_events = self.user_events.lock();
+            if let Some(event) = user_events.pop_front() {
+                // The senders woke the source after queueing. Those wakes are handled once the
+                // queue is empty and would otherwise interrupt the next read.
+                if user_events.is_empty() {
+                    self.source.clear_wake();
+                }
+                return Ok(Some(event));
+            }
+        }
+        if let Some(token) = self.expire_timer() {
+            return Ok(Some(Event::Timer(token)));
+        }
+        let timeout = match self.timers.iter().map(|(deadline, _)| *deadline).min() {
+            Some(deadline) => {
+                let until_deadline = deadline.saturating_duration_since(Instant::now());
+                Some(timeout.map_or(until_deadline, |timeout| timeout.min(until_deadline)))
+            }
+            None => timeout,
+        };
+        let event = match self.source.try_read(timeout) {
+            Ok(Some(event)) => Some(event),
+            Ok(None) => self.expire_timer().map(Event::Timer),
+            // The source was woken. This is an error unless a user event was sent.
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                match self.user_events.lock().pop_front() {
+                    Some(event) => return Ok(Some(event)),
+                    None => return Err(err),
+                }
+            }
+            Err(err) => return Err(err),
+        };
+        if matches!(event, Some(Event::WindowResized(_))) {
+            self.resizes += 1;
+            self.pixel_size = None;
+        }
+        Ok(event)
+    }
+
+    /// Removes the timer with the earliest deadline if it has expired.
+    fn expire_timer(&mut self) -> Option<Token> {
+        let now = Instant::now();
+        let (i, _) = self
+            .timers
+            .iter()
+            .enumerate()
+            .filter(|(_, (deadline, _))| *deadline <= now)
+            .min_by_key(|(_, (deadline, _))| *deadline)?;
+        Some(self.timers.remove(i).1)
+    }
+
+    /// Re-arms the FDs whose `Event::Readable` has been read. See `EventReader::take`.
+    #[cfg(unix)]
+    fn rearm_fds(&mut self) {
+        for token in self.rearm.lock().drain(..) {
+            self.source.rearm_fd(token);
+            self.changed = true;
+        }
+    }
+
+    /// Returns duplicates of the source's FDs unless their generation is `known`.
+    #[cfg(all(unix, any(feature = "tokio", feature = "async-io")))]
+    fn source_fds(&self, known: Option<u64>) -> Option<io::Result<(u64, Vec<OwnedFd>)>> {
+        let (generation, fds) = self.source.fds()?;
+        if known == Some(generation) {
+            return None;
+        }
+        let fds = fds
+            .iter()
+            .map(|fd| fd.try_clone_to_owned())
+            .collect::<io::Result<_>>();
+        Some(fds.map(|fds| (generation, fds)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use crate::{event::KeyCode, Terminal as _, VirtualTerminal, WindowSize};
+
+    use super::*;
+
+    #[test]
+    fn user_events_clear_wake() {
+        let terminal = VirtualTerminal::new(WindowSize::TEST);
+        let sender = terminal.event_reader().user_event_sender();
+        sender.send(1u8).unwrap();
+        sender.send(2u8).unwrap();
+        let is_user = |event: &Event| matches!(event, Event::User(_));
+        assert!(is_user(&terminal.read(is_user).unwrap()));
+        assert!(is_user(&terminal.read(is_user).unwrap()));
+
+        // The wakes were handled along with the events so a blocking poll waits for input.
+        let input = terminal.input();
+        let sender = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            input.send_bytes(b"q");
+        });
+        assert!(terminal.poll(|_| true, None).unwrap());
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn coalescing() {
+        let size = WindowSize::TEST;
+        let terminal = VirtualTerminal::new(size);
+        terminal.event_reader().set_coalescing(Coalescing {
+            resizes: true,
+            mouse_moves: true,
+            window: Duration::ZERO,
+        });
+        let input = terminal.input();
+        let resized = |cols| WindowSize { cols, ..size };
+        input.resize(resized(81));
+        input.resize(resized(82));
+        input.send_bytes(b"q");
+        input.resize(resized(83));
+        // Two mouse moves, which are merged, and then a drag. Columns are zero-based.
+        input.send_bytes(b"\x1b[<35;1;1M\x1b[<35;2;1M\x1b[<32;3;1M");
+        input.resize(resized(84));
+        input.resize(resized(85));
+
+        let mut events = Vec::new();
+        while terminal.poll(|_| true, Some(Duration::ZERO)).unwrap() {
+            events.push(terminal.read(|_| true).unwrap());
+        }
+        let columns: Vec<_> = events
+            .iter()
+            .map(|event| match event {
+                Event::WindowResized(size) => format!("resize {}", size.cols),
+                Event::Mouse(mouse) => format!("mouse {}", mouse.column),
+                Event::Key(_) => "key".to_string(),
+                event => panic!("unexpected event {event:?}"),
+            })
+            .collect();
+        assert_eq!(
+            columns,
+            [
+                "resize 82",
+                "key",
+                "resize 83",
+                "mouse 1",
+                "mouse 2",
+                "resize 85"
+            ]
+        );
+    }
+
+    #[test]
+    fn coalescing_window() {
+        let size = WindowSize::TEST;
+        let terminal = VirtualTerminal::new(size);
+        terminal.event_reader().set_coalescing(Coalescing {
+            resizes: true,
+            mouse_moves: false,
+            window: Duration::from_secs(60),
+        });
+        let input = terminal.input();
+        input.resize(WindowSize { cols: 81, ..size });
+        input.resize(WindowSize { cols: 82, ..size });
+
+        // The window doesn't delay a poll beyond its timeout but available events are merged.
+        let start = Instant::now();
+        assert!(terminal.poll(|_| true, Some(Duration::ZERO)).unwrap());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            terminal.read(|_| true).unwrap(),
+            Event::WindowResized(WindowSize { cols: 82, ..size })
+        );
+
+        input.resize(WindowSize { cols: 83, ..size });
+        let start = Instant::now();
+        assert!(terminal
+            .poll(|_| true, Some(Duration::from_millis(20)))
+            .unwrap());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn subscriptions() {
+        let terminal = VirtualTerminal::new(WindowSize::TEST);
+        let reader = terminal.event_reader();
+        let is_key = |event: &Event| matches!(event, Event::Key(_));
+        let recorder = reader.subscribe(
+            SubscriptionOptions {
+                capacity: 2,
+                lagged: Lagged::DropOldest,
+                exclusive: false,
+            },
+            is_key,
+        );
+        let replies = reader.subscribe(
+            SubscriptionOptions {
+                exclusive: true,
+                ..Default::default()
+            },
+            |event| event.is_escape(),
+        );
+
+        // Three keys with a cursor position report in between.
+        terminal.input().send_bytes(b"ab\x1b[12;5Rc");
+        let mut keys = 0;
+        while terminal.poll(|_| true, Some(Duration::ZERO)).unwrap() {
+            assert!(is_key(&terminal.read(|_| true).unwrap()));
+            keys += 1;
+        }
+        assert_eq!(keys, 3);
+
+        // The recorder only buffers the last two keys.
+        assert_eq!(recorder.take_lagged(), 1);
+        assert!(recorder.poll(Some(Duration::ZERO)).unwrap());
+        assert!(is_key(&recorder.read().unwrap()));
+        assert!(is_key(&recorder.read().unwrap()));
+        assert_eq!(recorder.try_read(), None);
+
+        // The reply was only delivered to the exclusive subscription.
+        assert!(matches!(replies.try_read(), Some(Event::Csi(_))));
+        assert_eq!(replies.try_read(), None);
+
+        // Subscriptions also read from the terminal themselves.
+        terminal.input().send_bytes(b"d");
+        assert!(is_key(&recorder.read().unwrap()));
+        assert!(terminal.poll(is_key, Some(Duration::ZERO)).unwrap());
+    }
+
+    #[test]
+    fn subscription_bounds_reader_queue() {
+        let terminal = VirtualTerminal::new(WindowSize::TEST);
+        let subscription = terminal.event_reader().subscribe(
+            SubscriptionOptions {
+                capacity: 2,
+                ..Default::default()
+            },
+            |_| true,
+        );
+        terminal.input().send_bytes(b"abcd");
+        for _ in 0..4 {
+            subscription.read().unwrap();
+        }
+
+        // Only the last two keys read by the subscription are queued for the reader.
+        let mut keys = Vec::new();
+        while terminal.poll(|_| true, Some(Duration::ZERO)).unwrap() {
+            match terminal.read(|_| true).unwrap() {
+                Event::Key(key) => keys.push(key.code),
+                event => panic!("unexpected event {event:?}"),
+            }
+        }
+        assert_eq!(keys, [KeyCode::Char('c'), KeyCode::Char('d')]);
     }
 }
@@ -1,19 +1,27 @@
 #[cfg(unix)]
 mod unix;
+mod virtual_input;
 #[cfg(windows)]
 mod windows;
 
-use std::time::{Duration, Instant};
+#[cfg(all(unix, any(feature = "tokio", feature = "async-io")))]
+use std::os::fd::BorrowedFd;
+#[cfg(unix)]
+use std::os::fd::OwnedFd;
+use std::{
+    fmt, io,
+    time::{Duration, Instant},
+};
 
 #[cfg(unix)]
-pub(crate) use unix::{UnixEventSource, UnixWaker};
-#[cfg(windows)]
-pub(crate) use windows::{WindowsEventSource, WindowsWaker};
+use super::Token;
 
 #[cfg(unix)]
-pub(crate) type PlatformEventSource = UnixEventSource;
+pub(crate) use unix::{is_foreground, poll, SignalWatcher, UnixEventSource, UnixWaker};
+pub use virtual_input::VirtualInput;
+pub(crate) use virtual_input::{VirtualEventSource, VirtualWaker};
 #[cfg(windows)]
-pub(crate) type PlatformEventSource = WindowsEventSource;
+pub(crate) use windows::{WindowsEventSource, WindowsWaker};
 
 #[cfg(unix)]
 pub(crate) type PlatformWaker = UnixWaker;
@@ -21,10 +29,73 @@ pub(crate) type PlatformWaker = UnixWaker;
 pub(crate) type PlatformWaker = WindowsWaker;
 
 // CREDIT: <https://github.com/crossterm-rs/crossterm/blob/36d95b26a26e64b0f8c12edfe11f410a6d56a812/src/event/source.rs#L12-L27>
-pub(crate) trait EventSource: Send + Sync {
+pub(crate) trait EventSource: Send + Sync + fmt::Debug {
     fn try_read(&mut self, timeout: Option<Duration>) -> std::io::Result<Option<crate::Event>>;
 
-    fn waker(&self) -> PlatformWaker;
+    fn waker(&self) -> Waker;
+
+    /// Discards a pending wake from the source's [Waker], so that the next `try_read` doesn't
+    /// return `Interrupted` for a wake which was already handled.
+    fn clear_wake(&mut self);
+
+    /// Polls `fd` along with the terminal, emitting `Event::Readable(token)` when it is readable.
+    #[cfg(unix)]
+    fn register_fd(&mut self, _fd: OwnedFd, _token: Token) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "this event source does not support registering file descriptors",
+        ))
+    }
+
+    #[cfg(unix)]
+    fn deregister_fd(&mut self, _token: Token) -> Option<OwnedFd> {
+        None
+    }
+
+    /// Resumes polling the FD registered with `token` once its `Event::Readable` has been read.
+    #[cfg(unix)]
+    fn rearm_fd(&mut self, _token: Token) {}
+
+    /// The FDs which `try_read` polls, for waiting on them with an async runtime's reactor
+    /// instead. Once none of them is readable `try_read` with a zero timeout returns `Ok(None)`.
+    ///
+    /// The number returned with the FDs changes whenever the set of FDs changes. Returns `None`
+    /// if the source can't be polled through FDs.
+    #[cfg(all(unix, any(feature = "tokio", feature = "async-io")))]
+    fn fds(&self) -> Option<(u64, Vec<BorrowedFd<'_>>)> {
+        None
+    }
+}
+
+/// Interrupts an `EventReader` which is blocked polling for events. See `EventReader::waker`.
+#[derive(Debug, Clone)]
+pub struct Waker(WakerKind);
+
+#[derive(Debug, Clone)]
+enum WakerKind {
+    Platform(PlatformWaker),
+    Virtual(VirtualWaker),
+}
+
+impl Waker {
+    pub fn wake(&self) -> io::Result<()> {
+        match &self.0 {
+            WakerKind::Platform(waker) => waker.wake(),
+            WakerKind::Virtual(waker) => waker.wake(),
+        }
+    }
+}
+
+impl From<PlatformWaker> for Waker {
+    fn from(waker: PlatformWaker) -> Self {
+        Self(WakerKind::Platform(waker))
+    }
+}
+
+impl From<VirtualWaker> for Waker {
+    fn from(waker: VirtualWaker) -> Self {
+        Self(WakerKind::Virtual(waker))
+    }
 }
 
 // CREDIT: <https://github.com/crossterm-rs/crossterm/blob/36d95b26a26e64b0f8c12edfe11f410a6d56a812/src/event/timeout.rs#L5-L40>
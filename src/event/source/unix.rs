@@ -6,19 +6,27 @@
 use std::{
     io::{self, Read, Write as _},
     os::{
-        fd::{AsFd, BorrowedFd},
+        fd::{AsFd, BorrowedFd, OwnedFd},
         unix::net::UnixStream,
     },
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::Duration,
 };
 
 use parking_lot::Mutex;
-use rustix::termios;
+use rustix::termios::{self, Termios};
 
-use crate::{parse::Parser, terminal::FileDescriptor, Event};
+use crate::{
+    event::{Signal, Token},
+    parse::Parser,
+    terminal::FileDescriptor,
+    Event, WindowSize,
+};
 
-use super::{EventSource, PollTimeout};
+use super::{EventSource, PollTimeout, Waker};
 
 #[derive(Debug)]
 pub struct UnixEventSource {
@@ -27,8 +35,121 @@ pub struct UnixEventSource {
     write: FileDescriptor,
     sigwinch_id: signal_hook::SigId,
     sigwinch_pipe: UnixStream,
+    /// Another handle to the write end of the SIGWINCH pipe for `UnixTerminal::notify_resized`.
+    resize_pipe: Arc<UnixStream>,
+    /// The last known size of the terminal. SIGWINCH is delivered process-wide so it may be for
+    /// a different terminal: the size is compared to tell whether this terminal was resized. See
+    /// also `Self::update_size`.
+    size: Option<WindowSize>,
+    /// A self-pipe shared by all watched signals other than SIGWINCH. See `SignalFlag`.
+    signal_pipe: UnixStream,
+    sigcont: SignalFlag,
+    signals: Arc<SignalWatcher>,
+    /// The termios set by `Terminal::enter_raw_mode` or `UnixTerminal::set_mode`, shared with
+    /// the `UnixTerminal`.
+    raw_termios: Arc<Mutex<Option<Termios>>>,
     wake_pipe: UnixStream,
     wake_pipe_write: Arc<Mutex<UnixStream>>,
+    input: InputState,
+    /// File descriptors registered with `EventReader::register_fd`.
+    user_fds: Vec<UserFd>,
+    /// Incremented whenever the polled FDs change. See `EventSource::fds`.
+    fds_generation: u64,
+}
+
+#[derive(Debug)]
+struct UserFd {
+    fd: OwnedFd,
+    token: Token,
+    /// Cleared once the FD was reported as readable until the `Event::Readable` has been read.
+    /// Otherwise the FD would be reported over and over while the event waits in the queue.
+    armed: bool,
+}
+
+/// Whether the terminal's input handle is still open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputState {
+    Open,
+    /// The handle reached EOF or was hung up but `Event::Closed` has not been emitted yet.
+    Eof,
+    /// `Event::Closed` has been emitted. The handle is no longer polled.
+    Closed,
+}
+
+/// A signal which is watched through the shared signal pipe.
+///
+/// The pipe only tells the reader that some signal arrived, so each signal also sets a flag
+/// which the reader swaps back once it has handled the signal.
+#[derive(Debug)]
+struct SignalFlag {
+    flag: Arc<AtomicBool>,
+    ids: [signal_hook::SigId; 2],
+}
+
+impl SignalFlag {
+    fn register(signal: std::ffi::c_int, pipe: &UnixStream) -> io::Result<Self> {
+        let flag = Arc::new(AtomicBool::new(false));
+        // Set the flag before writing to the pipe so that the flag is always visible to the
+        // reader once the pipe is readable.
+        let flag_id = signal_hook::flag::register(signal, flag.clone())?;
+        let pipe_id = signal_hook::low_level::pipe::register(signal, pipe.try_clone()?)?;
+        Ok(Self {
+            flag,
+            ids: [flag_id, pipe_id],
+        })
+    }
+
+    fn take(&self) -> bool {
+        self.flag.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl Drop for SignalFlag {
+    fn drop(&mut self) {
+        for id in self.ids {
+            signal_hook::low_level::unregister(id);
+        }
+    }
+}
+
+/// The signals which are delivered as `Event::Signal`.
+///
+/// This is shared with the `UnixTerminal` so that signals can be watched while another thread
+/// is polling the event source.
+#[derive(Debug)]
+pub(crate) struct SignalWatcher {
+    /// The write end of the event source's signal pipe.
+    pipe: UnixStream,
+    watched: Mutex<Vec<(Signal, SignalFlag)>>,
+}
+
+impl SignalWatcher {
+    pub(crate) fn watch(&self, signal: Signal) -> io::Result<()> {
+        use signal_hook::consts::*;
+
+        let mut watched = self.watched.lock();
+        if watched.iter().any(|(watched, _)| *watched == signal) {
+            return Ok(());
+        }
+        let raw = match signal {
+            Signal::Terminate => SIGTERM,
+            Signal::Interrupt => SIGINT,
+            Signal::Hangup => SIGHUP,
+            Signal::Quit => SIGQUIT,
+            Signal::User1 => SIGUSR1,
+            Signal::User2 => SIGUSR2,
+        };
+        watched.push((signal, SignalFlag::register(raw, &self.pipe)?));
+        Ok(())
+    }
+
+    fn take(&self) -> Option<Signal> {
+        self.watched
+            .lock()
+            .iter()
+            .find(|(_, flag)| flag.take())
+            .map(|(signal, _)| *signal)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -43,16 +164,30 @@ impl UnixWaker {
 }
 
 impl UnixEventSource {
-    pub(crate) fn new(read: FileDescriptor, write: FileDescriptor) -> io::Result<Self> {
+    pub(crate) fn new(
+        read: FileDescriptor,
+        write: FileDescriptor,
+        raw_termios: Arc<Mutex<Option<Termios>>>,
+    ) -> io::Result<Self> {
         let (sigwinch_pipe, sigwinch_pipe_write) = UnixStream::pair()?;
+        sigwinch_pipe_write.set_nonblocking(true)?;
+        let resize_pipe = Arc::new(sigwinch_pipe_write.try_clone()?);
         let sigwinch_id = signal_hook::low_level::pipe::register(
             signal_hook::consts::SIGWINCH,
             sigwinch_pipe_write,
         )?;
         sigwinch_pipe.set_nonblocking(true)?;
+        let (signal_pipe, signal_pipe_write) = UnixStream::pair()?;
+        signal_pipe.set_nonblocking(true)?;
+        let sigcont = SignalFlag::register(signal_hook::consts::SIGCONT, &signal_pipe_write)?;
+        let signals = Arc::new(SignalWatcher {
+            pipe: signal_pipe_write,
+            watched: Mutex::new(Vec::new()),
+        });
         let (wake_pipe, wake_pipe_write) = UnixStream::pair()?;
         wake_pipe.set_nonblocking(true)?;
         wake_pipe_write.set_nonblocking(true)?;
+        let size = termios::tcgetwinsize(&write).ok().map(WindowSize::from);
 
         Ok(Self {
             parser: Default::default(),
@@ -60,12 +195,128 @@ impl UnixEventSource {
             write,
             sigwinch_id,
             sigwinch_pipe,
+            resize_pipe,
+            size,
+            signal_pipe,
+            sigcont,
+            signals,
+            raw_termios,
             wake_pipe,
             wake_pipe_write: Arc::new(Mutex::new(wake_pipe_write)),
+            input: InputState::Open,
+            user_fds: Vec::new(),
+            fds_generation: 0,
         })
     }
 }
 
+impl UnixEventSource {
+    pub(crate) fn signal_watcher(&self) -> Arc<SignalWatcher> {
+        self.signals.clone()
+    }
+
+    /// A handle which makes the source check the terminal's size as if SIGWINCH was received
+    /// when a byte is written to it.
+    pub(crate) fn resize_pipe(&self) -> Arc<UnixStream> {
+        self.resize_pipe.clone()
+    }
+
+    /// The terminal's input handle, the self-pipes and the armed user FDs, in that order.
+    fn polled_fds(&self) -> Vec<BorrowedFd<'_>> {
+        let mut fds = vec![
+            self.read.as_fd(),
+            self.sigwinch_pipe.as_fd(),
+            self.signal_pipe.as_fd(),
+            self.wake_pipe.as_fd(),
+        ];
+        fds.extend(
+            self.user_fds
+                .iter()
+                .filter(|user_fd| user_fd.armed)
+                .map(|user_fd| user_fd.fd.as_fd()),
+        );
+        fds
+    }
+
+    /// Handles any signals received through the signal pipe.
+    fn take_signal_event(&mut self) -> io::Result<Option<Event>> {
+        if self.sigcont.take() {
+            // The terminal was probably returned to cooked mode while the process was stopped.
+            // Re-apply raw mode, but only from the foreground: changing the termios from the
+            // background would stop the process again with SIGTTOU. Resuming into the foreground
+            // (`fg`) sends another SIGCONT.
+            if let Some(raw_termios) = self.raw_termios.lock().as_ref() {
+                if is_foreground(&self.write)? {
+                    termios::tcsetattr(&self.write, termios::OptionalActions::Now, raw_termios)?;
+                }
+            }
+            return Ok(Some(Event::Resumed));
+        }
+        Ok(self.signals.take().map(Event::Signal))
+    }
+
+    /// Pops the next event from the parser, dropping in-band resize notifications which
+    /// repeat a size already reported by SIGWINCH.
+    fn pop_event(&mut self) -> Option<Event> {
+        loop {
+            match self.parser.pop()? {
+                Event::WindowResized(size) => {
+                    if let Some(size) = self.update_size(size) {
+                        return Some(Event::WindowResized(size));
+                    }
+                }
+                event => return Some(event),
+            }
+        }
+    }
+
+    /// Records a new size of the terminal, returning the size if it differs from the last known
+    /// size.
+    ///
+    /// With `DecPrivateModeCode::InBandResize` enabled a resize is reported both by SIGWINCH and
+    /// in-band, and only the first report is passed on. Pixel dimensions which are unknown on
+    /// either side match any pixel dimensions: SIGWINCH usually can't tell the pixel size over
+    /// SSH while the in-band report always includes it. Pixel dimensions learned from a repeated
+    /// report are still recorded so that later reports are compared against them.
+    fn update_size(&mut self, size: WindowSize) -> Option<WindowSize> {
+        let matches =
+            |known: Option<u16>, new: Option<u16>| known.is_none() || new.is_none() || new == known;
+        if let Some(last) = self.size.as_mut().filter(|last| {
+            last.cols == size.cols
+                && last.rows == size.rows
+                && matches(last.pixel_width, size.pixel_width)
+                && matches(last.pixel_height, size.pixel_height)
+        }) {
+            last.pixel_width = size.pixel_width.or(last.pixel_width);
+            last.pixel_height = size.pixel_height.or(last.pixel_height);
+            return None;
+        }
+        self.size = Some(size);
+        Some(size)
+    }
+
+    /// Reads from the terminal's input handle, returning `None` at EOF or when the terminal has
+    /// been hung up.
+    fn read_input(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        loop {
+            match self.read.read(buf) {
+                Ok(0) => return Ok(None),
+                Ok(read) => return Ok(Some(read)),
+                Err(err) => match err.kind() {
+                    io::ErrorKind::WouldBlock => return Ok(Some(0)),
+                    io::ErrorKind::Interrupted => continue,
+                    // Reading from a hung up terminal fails with EIO rather than returning EOF
+                    // on some platforms, for example a PTY whose controller side was closed.
+                    _ if err.raw_os_error() == Some(rustix::io::Errno::IO.raw_os_error()) => {
+                        return Ok(None)
+                    }
+                    _ => return Err(err),
+                },
+            }
+        }
+    }
+}
+
 impl Drop for UnixEventSource {
     fn drop(&mut self) {
         signal_hook::low_level::unregister(self.sigwinch_id);
@@ -73,42 +324,118 @@ impl Drop for UnixEventSource {
 }
 
 impl EventSource for UnixEventSource {
-    fn waker(&self) -> UnixWaker {
+    fn waker(&self) -> Waker {
         UnixWaker {
             inner: self.wake_pipe_write.clone(),
         }
+        .into()
+    }
+
+    fn clear_wake(&mut self) {
+        // A failed read leaves the wake pending, which at worst interrupts a poll spuriously.
+        while read_complete(&self.wake_pipe, &mut [0; 1024]).is_ok_and(|read| read != 0) {}
+    }
+
+    fn register_fd(&mut self, fd: OwnedFd, token: Token) -> io::Result<()> {
+        if self.user_fds.iter().any(|user_fd| user_fd.token == token) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "a file descriptor is already registered with this token",
+            ));
+        }
+        self.user_fds.push(UserFd {
+            fd,
+            token,
+            armed: true,
+        });
+        self.fds_generation += 1;
+        Ok(())
+    }
+
+    fn deregister_fd(&mut self, token: Token) -> Option<OwnedFd> {
+        let index = self
+            .user_fds
+            .iter()
+            .position(|user_fd| user_fd.token == token)?;
+        self.fds_generation += 1;
+        Some(self.user_fds.remove(index).fd)
+    }
+
+    fn rearm_fd(&mut self, token: Token) {
+        if let Some(user_fd) = self
+            .user_fds
+            .iter_mut()
+            .find(|user_fd| user_fd.token == token && !user_fd.armed)
+        {
+            user_fd.armed = true;
+            self.fds_generation += 1;
+        }
+    }
+
+    // Like `poll` below, kqueue doesn't support the terminal device on macOS.
+    #[cfg(all(any(feature = "tokio", feature = "async-io"), not(target_os = "macos")))]
+    fn fds(&self) -> Option<(u64, Vec<BorrowedFd<'_>>)> {
+        Some((self.fds_generation, self.polled_fds()))
     }
 
     fn try_read(&mut self, timeout: Option<Duration>) -> io::Result<Option<Event>> {
         let timeout = PollTimeout::new(timeout);
 
         loop {
-            if let Some(event) = self.parser.pop() {
+            if let Some(event) = self.pop_event() {
                 return Ok(Some(event));
             }
+            // More than one signal may arrive before the pipe is drained so check for signals
+            // on each iteration.
+            if let Some(event) = self.take_signal_event()? {
+                return Ok(Some(event));
+            }
+            match self.input {
+                InputState::Open => (),
+                InputState::Eof => {
+                    self.input = InputState::Closed;
+                    return Ok(Some(Event::Closed));
+                }
+                // Polling a closed handle would return immediately, spinning the caller.
+                InputState::Closed => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "the terminal's input was closed",
+                    ))
+                }
+            }
 
-            let [read_ready, sigwinch_ready, wake_ready] = match poll(
-                [
-                    self.read.as_fd(),
-                    self.sigwinch_pipe.as_fd(),
-                    self.wake_pipe.as_fd(),
-                ],
-                timeout.leftover(),
-            ) {
+            let ready = match poll(&self.polled_fds(), timeout.leftover()) {
                 Ok(ready) => ready,
                 Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
                 Err(err) => return Err(err),
             };
-
-            // The input/read pipe has data.
+            let [read_ready, sigwinch_ready, signal_ready, wake_ready] =
+                [ready[0], ready[1], ready[2], ready[3]];
+            // The ready user FDs are in the same order as the armed user FDs.
+            let user_fd_ready = self
+                .user_fds
+                .iter()
+                .enumerate()
+                .filter(|(_, user_fd)| user_fd.armed)
+                .zip(&ready[4..])
+                .find_map(|((i, _), ready)| ready.then_some(i));
+
+            // The input/read pipe has data or was closed.
             if read_ready {
                 let mut buffer = [0u8; 64];
-                let read_count = read_complete(&mut self.read, &mut buffer)?;
+                let Some(read_count) = self.read_input(&mut buffer)? else {
+                    // Flush any partial sequence, for example a lone ESC, before reporting
+                    // `Event::Closed` at the top of the loop.
+                    self.parser.parse(&[], false);
+                    self.input = InputState::Eof;
+                    continue;
+                };
                 if read_count > 0 {
                     self.parser
                         .parse(&buffer[..read_count], read_count == buffer.len());
                 }
-                if let Some(event) = self.parser.pop() {
+                if let Some(event) = self.pop_event() {
                     return Ok(Some(event));
                 }
                 if read_count == 0 {
@@ -116,14 +443,31 @@ impl EventSource for UnixEventSource {
                 }
             }
 
-            // SIGWINCH received.
+            // SIGWINCH received or `UnixTerminal::notify_resized` called.
             if sigwinch_ready {
                 // Drain the pipe.
                 while read_complete(&self.sigwinch_pipe, &mut [0; 1024])? != 0 {}
 
-                let winsize = termios::tcgetwinsize(&self.write)?;
-                let event = Event::WindowResized(winsize.into());
-                return Ok(Some(event));
+                let size = termios::tcgetwinsize(&self.write)?.into();
+                if let Some(size) = self.update_size(size) {
+                    return Ok(Some(Event::WindowResized(size)));
+                }
+            }
+
+            // Another watched signal was received.
+            if signal_ready {
+                // Drain the pipe.
+                while read_complete(&self.signal_pipe, &mut [0; 1024])? != 0 {}
+                // The flags are checked at the top of the loop.
+                continue;
+            }
+
+            // A registered FD is readable. It isn't polled again until the event is read.
+            if let Some(i) = user_fd_ready {
+                let user_fd = &mut self.user_fds[i];
+                user_fd.armed = false;
+                self.fds_generation += 1;
+                return Ok(Some(Event::Readable(user_fd.token)));
             }
 
             // Waker has awoken.
@@ -146,6 +490,18 @@ impl EventSource for UnixEventSource {
     }
 }
 
+/// Whether the process is in the foreground process group of the terminal.
+///
+/// This is `false` for a terminal which isn't the process's controlling terminal, for example a
+/// PTY passed to `UnixTerminal::from_fds`.
+pub(crate) fn is_foreground(fd: impl AsFd) -> io::Result<bool> {
+    match termios::tcgetpgrp(fd) {
+        Ok(pgrp) => Ok(pgrp == rustix::process::getpgrp()),
+        Err(rustix::io::Errno::NOTTY) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
 fn read_complete<F: Read>(mut file: F, buf: &mut [u8]) -> io::Result<usize> {
     loop {
         match file.read(buf) {
@@ -166,53 +522,51 @@ fn read_complete<F: Read>(mut file: F, buf: &mut [u8]) -> io::Result<usize> {
 /// `select(2)` we want. Specifically we are looking for `POLLIN` events from `poll(2)` and we
 /// consider that to be "ready."
 ///
-/// This module is not meant to be generic. We consider `POLLIN` to be "ready" and do not look at
-/// other poll flags. For the sake of simplicity we also only allow polling exactly three FDs at
-/// a time - the exact amount we need for the event source.
-fn poll(fds: [BorrowedFd<'_>; 3], timeout: Option<Duration>) -> std::io::Result<[bool; 3]> {
+/// This module is not meant to be generic. We consider `POLLIN` to be "ready" and also treat
+/// `POLLHUP`, `POLLERR` and `POLLNVAL` as "ready" since those are reported even when not
+/// requested: a read from the FD then returns EOF or the error. (`select(2)` already reports
+/// these conditions as readable.) Returns whether each FD is ready, in the same order as `fds`.
+pub(crate) fn poll(fds: &[BorrowedFd<'_>], timeout: Option<Duration>) -> io::Result<Vec<bool>> {
     use rustix::event::Timespec;
 
     #[cfg(not(target_os = "macos"))]
-    fn poll2(fds: [BorrowedFd<'_>; 3], timeout: Option<&Timespec>) -> io::Result<[bool; 3]> {
+    fn poll2(fds: &[BorrowedFd<'_>], timeout: Option<&Timespec>) -> io::Result<Vec<bool>> {
         use rustix::event::{PollFd, PollFlags};
-        let mut fds = [
-            PollFd::new(&fds[0], PollFlags::IN),
-            PollFd::new(&fds[1], PollFlags::IN),
-            PollFd::new(&fds[2], PollFlags::IN),
-        ];
+        let mut fds: Vec<PollFd> = fds
+            .iter()
+            .map(|fd| PollFd::new(fd, PollFlags::IN))
+            .collect();
 
         rustix::event::poll(&mut fds, timeout)?;
 
-        Ok([
-            fds[0].revents().contains(PollFlags::IN),
-            fds[1].revents().contains(PollFlags::IN),
-            fds[2].revents().contains(PollFlags::IN),
-        ])
+        Ok(fds
+            .iter()
+            .map(|fd| {
+                fd.revents()
+                    .intersects(PollFlags::IN | PollFlags::HUP | PollFlags::ERR | PollFlags::NVAL)
+            })
+            .collect())
     }
 
     #[cfg(target_os = "macos")]
-    fn select2(fds: [BorrowedFd<'_>; 3], timeout: Option<&Timespec>) -> io::Result<[bool; 3]> {
+    fn select2(fds: &[BorrowedFd<'_>], timeout: Option<&Timespec>) -> io::Result<Vec<bool>> {
         use rustix::event::{fd_set_insert, fd_set_num_elements, FdSetElement, FdSetIter};
         use std::os::fd::AsRawFd;
 
-        let fds = [fds[0].as_raw_fd(), fds[1].as_raw_fd(), fds[2].as_raw_fd()];
-        // The array is non-empty so `max()` cannot return `None`.
-        let nfds = fds.iter().copied().max().unwrap() + 1;
+        let fds: Vec<_> = fds.iter().map(|fd| fd.as_raw_fd()).collect();
+        let nfds = fds.iter().copied().max().map_or(0, |fd| fd + 1);
 
         let mut readfds = vec![FdSetElement::default(); fd_set_num_elements(fds.len(), nfds)];
-        for fd in fds {
+        for &fd in &fds {
             fd_set_insert(&mut readfds, fd);
         }
 
         unsafe { rustix::event::select(nfds, Some(&mut readfds), None, None, timeout) }?;
 
-        let mut ready = [false; 3];
-        for (fd, is_ready) in fds.iter().copied().zip(ready.iter_mut()) {
-            if FdSetIter::new(&readfds).any(|set_fd| set_fd == fd) {
-                *is_ready = true;
-            }
-        }
-        Ok(ready)
+        Ok(fds
+            .iter()
+            .map(|&fd| FdSetIter::new(&readfds).any(|set_fd| set_fd == fd))
+            .collect())
     }
 
     #[cfg(not(target_os = "macos"))]
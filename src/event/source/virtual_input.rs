@@ -0,0 +1,177 @@
+// The event source for `VirtualTerminal`. Input is queued in memory by a `VirtualInput` handle
+// instead of being read from a file descriptor or console handle, and the reader waits on a
+// condition variable rather than polling.
+
+use std::{collections::VecDeque, io, sync::Arc, time::Duration};
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::{parse::Parser, Event, WindowSize};
+
+use super::{EventSource, PollTimeout, Waker};
+
+/// A handle for sending input to a `VirtualTerminal`.
+///
+/// Input is read in the order it was sent by the terminal's `EventReader`, including any
+/// `EventStream` created from the reader. This type is cheap to clone.
+#[derive(Debug, Clone)]
+pub struct VirtualInput {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+#[derive(Debug)]
+struct State {
+    parser: Parser,
+    events: VecDeque<Event>,
+    size: WindowSize,
+    woken: bool,
+    closed: bool,
+}
+
+impl VirtualInput {
+    pub(crate) fn new(size: WindowSize) -> Self {
+        let state = State {
+            parser: Parser::default(),
+            events: VecDeque::new(),
+            size,
+            woken: false,
+            closed: false,
+        };
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(state),
+                condvar: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Sends raw input as if it was typed into the terminal, for example `b"\x1b[A"` for the up
+    /// arrow key.
+    ///
+    /// The bytes are parsed immediately so each call should contain complete sequences: a lone
+    /// ESC byte is read as the Escape key.
+    pub fn send_bytes(&self, bytes: &[u8]) {
+        self.update(|state| {
+            state.parser.parse(bytes, false);
+            while let Some(event) = state.parser.pop() {
+                state.events.push_back(event);
+            }
+        });
+    }
+
+    /// Sends an event to the terminal's reader.
+    pub fn send_event(&self, event: Event) {
+        self.update(|state| state.events.push_back(event));
+    }
+
+    /// Changes the dimensions of the terminal and sends an `Event::WindowResized`.
+    pub fn resize(&self, size: WindowSize) {
+        self.update(|state| {
+            state.size = size;
+            state.events.push_back(Event::WindowResized(size));
+        });
+    }
+
+    /// Closes the terminal's input, as if the terminal was hung up.
+    ///
+    /// Once the input sent before closing has been read the reader emits `Event::Closed` and
+    /// then fails with `UnexpectedEof` errors, the same as `UnixTerminal`. Input sent after
+    /// closing is ignored.
+    pub fn close(&self) {
+        self.update(|state| {
+            state.events.push_back(Event::Closed);
+            state.closed = true;
+        });
+    }
+
+    /// Changes the state and wakes the reader, all under one lock so that the reader never sees
+    /// an event without the state change which it reports. Does nothing once closed.
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        let mut state = self.shared.state.lock();
+        if state.closed {
+            return;
+        }
+        f(&mut state);
+        self.shared.condvar.notify_all();
+    }
+
+    pub(crate) fn size(&self) -> WindowSize {
+        self.shared.state.lock().size
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct VirtualEventSource {
+    input: VirtualInput,
+}
+
+impl VirtualEventSource {
+    pub(crate) fn new(input: VirtualInput) -> Self {
+        Self { input }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct VirtualWaker {
+    shared: Arc<Shared>,
+}
+
+impl VirtualWaker {
+    pub fn wake(&self) -> io::Result<()> {
+        self.shared.state.lock().woken = true;
+        self.shared.condvar.notify_all();
+        Ok(())
+    }
+}
+
+impl EventSource for VirtualEventSource {
+    fn waker(&self) -> Waker {
+        VirtualWaker {
+            shared: self.input.shared.clone(),
+        }
+        .into()
+    }
+
+    fn clear_wake(&mut self) {
+        self.input.shared.state.lock().woken = false;
+    }
+
+    fn try_read(&mut self, timeout: Option<Duration>) -> io::Result<Option<Event>> {
+        let timeout = PollTimeout::new(timeout);
+        let shared = &self.input.shared;
+        let mut state = shared.state.lock();
+
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                return Ok(Some(event));
+            }
+            if state.woken {
+                state.woken = false;
+                return Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "Poll operation was woken up",
+                ));
+            }
+            if state.closed {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the terminal's input was closed",
+                ));
+            }
+
+            match timeout.leftover() {
+                None => shared.condvar.wait(&mut state),
+                Some(timeout) if timeout.is_zero() => return Ok(None),
+                Some(timeout) => {
+                    shared.condvar.wait_for(&mut state, timeout);
+                }
+            }
+        }
+    }
+}
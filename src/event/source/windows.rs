@@ -9,7 +9,7 @@ use windows_sys::Win32::System::Threading;
 
 use crate::{event::Event, parse::Parser, terminal::InputHandle};
 
-use super::{EventSource, PollTimeout};
+use super::{EventSource, PollTimeout, Waker};
 
 #[derive(Debug)]
 pub struct WindowsEventSource {
@@ -29,10 +29,15 @@ impl WindowsEventSource {
 }
 
 impl EventSource for WindowsEventSource {
-    fn waker(&self) -> WindowsWaker {
+    fn waker(&self) -> Waker {
         WindowsWaker {
             handle: self.waker.clone(),
         }
+        .into()
+    }
+
+    fn clear_wake(&mut self) {
+        unsafe { Threading::ResetEvent(self.waker.as_raw_handle()) };
     }
 
     fn try_read(&mut self, timeout: Option<Duration>) -> io::Result<Option<Event>> {
@@ -112,7 +117,7 @@ impl AsRawHandle for EventHandle {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WindowsWaker {
     handle: Arc<EventHandle>,
 }
@@ -3,7 +3,7 @@
 // I added the dummy stream for integration testing in Helix.
 
 use std::{
-    io,
+    fmt, future, io,
     pin::Pin,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -12,12 +12,21 @@ use std::{
     },
     task::{Context, Poll},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use futures_core::Stream;
+use parking_lot::Mutex;
 
-use super::{reader::EventReader, source::PlatformWaker, Event};
+use crate::terminal::query::{self, Replies};
+use crate::{Query, QueryReply};
+
+use super::{reader::EventReader, source::Waker, Event};
+
+#[cfg(all(unix, any(feature = "tokio", feature = "async-io")))]
+mod reactor;
+
+type Filter = Arc<dyn Fn(&Event) -> bool + Send + Sync>;
 
 /// A stream of `termina::Event`s received from the terminal.
 ///
@@ -25,66 +34,326 @@ use super::{reader::EventReader, source::PlatformWaker, Event};
 ///
 /// Create an event stream for a terminal by passing the reader [crate::Terminal::event_reader]
 /// into [EventStream::new] with a filter.
+///
+/// With the `async-io` feature enabled the stream waits for input with the async-io reactor and
+/// reads from the terminal inline when it is polled. With the `tokio` feature a stream created
+/// with `EventStream::with_tokio` does the same with the tokio reactor. Otherwise, and for
+/// terminals which can't be polled that way like `VirtualTerminal`, each stream reads from the
+/// terminal on a helper thread which blocks in [EventReader::poll] while the stream is pending.
+///
+/// Several streams and threads can read from one reader: an event accepted by one stream's
+/// filter which was read by another is queued for it and the stream is woken.
+///
+/// Queries can be sent without stopping the stream with [Self::query] and [Self::query_event].
 pub struct EventStream {
-    waker: PlatformWaker,
-    filter: Arc<dyn Fn(&Event) -> bool>,
+    filter: Filter,
     reader: EventReader,
-    stream_wake_task_executed: Arc<AtomicBool>,
-    stream_wake_task_should_shutdown: Arc<AtomicBool>,
+    driver: Driver,
+    /// Whether the stream was created with `EventStream::with_tokio`.
+    tokio: bool,
+}
+
+/// How an [EventStream] waits for events.
+enum Driver {
+    /// The stream hasn't been polled yet. The driver is picked on the first poll so that it runs
+    /// within the async runtime.
+    Idle,
+    Thread(HelperThread),
+    #[cfg(all(unix, feature = "tokio"))]
+    Tokio(reactor::Reactor<reactor::Tokio>),
+    #[cfg(all(unix, feature = "async-io"))]
+    AsyncIo(reactor::Reactor<reactor::AsyncIo>),
+}
+
+impl Driver {
+    #[cfg_attr(not(all(unix, feature = "tokio")), allow(unused_variables))]
+    fn start(reader: &EventReader, tokio: bool) -> Self {
+        #[cfg(all(unix, feature = "tokio"))]
+        if tokio && tokio::runtime::Handle::try_current().is_ok() {
+            if let Some(reactor) = reactor::Reactor::new(reader) {
+                return Self::Tokio(reactor);
+            }
+        }
+        #[cfg(all(unix, feature = "async-io"))]
+        if let Some(reactor) = reactor::Reactor::new(reader) {
+            return Self::AsyncIo(reactor);
+        }
+        Self::Thread(HelperThread::spawn(reader.clone()))
+    }
+}
+
+/// A thread which polls the reader on behalf of the stream and wakes the stream's task once
+/// the reader has an event for it.
+struct HelperThread {
+    waker: Waker,
+    wake_task: Arc<WakeTask>,
     task_sender: SyncSender<Task>,
 }
 
-#[derive(Debug)]
+/// The state shared between a stream and its helper thread.
+struct WakeTask {
+    /// Whether the helper thread is polling the reader.
+    executed: AtomicBool,
+    should_shutdown: AtomicBool,
+    /// The filter the helper thread is polling with.
+    filter: Mutex<Option<Filter>>,
+    /// Woken when the helper thread stops polling. This is updated while the helper thread polls
+    /// so that the latest task is woken.
+    waker: Mutex<Option<std::task::Waker>>,
+}
+
 struct Task {
-    stream_waker: std::task::Waker,
-    stream_wake_task_executed: Arc<AtomicBool>,
-    stream_wake_task_should_shutdown: Arc<AtomicBool>,
+    filter: Filter,
+    deadline: Option<Instant>,
 }
 
-impl EventStream {
-    pub fn new<F>(reader: EventReader, filter: F) -> Self
-    where
-        F: Fn(&Event) -> bool + Send + Sync + 'static,
-    {
-        let filter = Arc::new(filter);
+impl HelperThread {
+    fn spawn(reader: EventReader) -> Self {
         let waker = reader.waker();
+        let wake_task = Arc::new(WakeTask {
+            executed: AtomicBool::new(false),
+            should_shutdown: AtomicBool::new(false),
+            filter: Mutex::new(None),
+            waker: Mutex::new(None),
+        });
 
         let (task_sender, receiver) = mpsc::sync_channel::<Task>(1);
 
-        let task_reader = reader.clone();
-        let task_filter = filter.clone();
+        let task_state = wake_task.clone();
         thread::spawn(move || {
             while let Ok(task) = receiver.recv() {
                 loop {
-                    if let Ok(true) = task_reader.poll(None, &*task_filter) {
+                    let timeout = task
+                        .deadline
+                        .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+                    // On an error wake the stream so that it can report the error.
+                    if !matches!(reader.poll(timeout, &*task.filter), Ok(false)) {
+                        break;
+                    }
+                    if task_state.should_shutdown.load(Ordering::SeqCst) {
                         break;
                     }
-                    if task.stream_wake_task_should_shutdown.load(Ordering::SeqCst) {
+                    if task
+                        .deadline
+                        .is_some_and(|deadline| Instant::now() >= deadline)
+                    {
                         break;
                     }
                 }
-                task.stream_wake_task_executed
-                    .store(false, Ordering::SeqCst);
-                task.stream_waker.wake();
+                *task_state.filter.lock() = None;
+                task_state.executed.store(false, Ordering::SeqCst);
+                if let Some(waker) = task_state.waker.lock().take() {
+                    waker.wake();
+                }
             }
         });
 
         Self {
             waker,
-            filter,
-            reader,
-            stream_wake_task_executed: Default::default(),
-            stream_wake_task_should_shutdown: Default::default(),
+            wake_task,
             task_sender,
         }
     }
+
+    fn poll(
+        &self,
+        cx: &mut Context<'_>,
+        reader: &EventReader,
+        filter: &Filter,
+        deadline: Option<Instant>,
+    ) -> Poll<io::Result<Event>> {
+        // The event is taken without waiting for the lock on the reader, which another stream's
+        // helper thread may hold while it blocks.
+        if let Err(err) = reader.poll(Some(Duration::from_secs(0)), &**filter) {
+            return Poll::Ready(Err(err));
+        }
+        if let Some(event) = reader.take(&**filter) {
+            return Poll::Ready(Ok(event));
+        }
+
+        let task = &self.wake_task;
+        *task.waker.lock() = Some(cx.waker().clone());
+        if task
+            .executed
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            task.should_shutdown.store(false, Ordering::SeqCst);
+            *task.filter.lock() = Some(filter.clone());
+            let _ = self.task_sender.send(Task {
+                filter: filter.clone(),
+                deadline,
+            });
+        } else if !task
+            .filter
+            .lock()
+            .as_ref()
+            .is_some_and(|polling| Arc::ptr_eq(polling, filter))
+        {
+            // The helper thread is polling for something else, for example the replies of a
+            // query which was cancelled. Stop it: it wakes this task once it has stopped and the
+            // task is sent again then.
+            task.should_shutdown.store(true, Ordering::SeqCst);
+            let _ = self.waker.wake();
+        }
+        Poll::Pending
+    }
 }
 
-impl Drop for EventStream {
+impl Drop for HelperThread {
     fn drop(&mut self) {
-        self.stream_wake_task_should_shutdown
-            .store(true, Ordering::SeqCst);
-        let _ = self.waker.wake();
+        self.wake_task.should_shutdown.store(true, Ordering::SeqCst);
+        // The helper thread exits once `task_sender` is dropped if it is waiting for a task. Only
+        // interrupt the reader if the helper thread is polling it, as the wake would otherwise
+        // interrupt whichever thread (or other stream) is polling the shared reader instead.
+        if self.wake_task.executed.load(Ordering::SeqCst) {
+            let _ = self.waker.wake();
+        }
+    }
+}
+
+impl EventStream {
+    pub fn new<F>(reader: EventReader, filter: F) -> Self
+    where
+        F: Fn(&Event) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            filter: Arc::new(filter),
+            reader,
+            driver: Driver::Idle,
+            tokio: false,
+        }
+    }
+
+    /// Creates a stream which waits for input with the tokio reactor.
+    ///
+    /// This type is only available if the `tokio` feature is enabled. The stream must be polled
+    /// within a tokio runtime which has IO and time enabled, otherwise polling it panics like
+    /// tokio's own IO types. Outside of a runtime and for terminals which can't be polled with
+    /// the reactor the stream behaves like one created with [Self::new].
+    #[cfg(all(unix, feature = "tokio"))]
+    pub fn with_tokio<F>(reader: EventReader, filter: F) -> Self
+    where
+        F: Fn(&Event) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            tokio: true,
+            ..Self::new(reader, filter)
+        }
+    }
+
+    /// Reads the next event accepted by `filter` if one is available, otherwise arranges for the
+    /// task to be woken once one is or once `deadline` has passed.
+    fn poll_filter(
+        &mut self,
+        cx: &mut Context<'_>,
+        filter: &Filter,
+        deadline: Option<Instant>,
+    ) -> Poll<io::Result<Event>> {
+        if matches!(self.driver, Driver::Idle) {
+            self.driver = Driver::start(&self.reader, self.tokio);
+        }
+        match &mut self.driver {
+            Driver::Idle => unreachable!(),
+            Driver::Thread(thread) => thread.poll(cx, &self.reader, filter, deadline),
+            #[cfg(all(unix, feature = "tokio"))]
+            Driver::Tokio(reactor) => reactor.poll(cx, &self.reader, filter, deadline),
+            #[cfg(all(unix, feature = "async-io"))]
+            Driver::AsyncIo(reactor) => reactor.poll(cx, &self.reader, filter, deadline),
+        }
+    }
+
+    /// Reads the next event accepted by `filter`, returning `None` once `deadline` has passed.
+    async fn read_until(
+        &mut self,
+        filter: &Filter,
+        deadline: Instant,
+    ) -> io::Result<Option<Event>> {
+        future::poll_fn(|cx| {
+            if let Poll::Ready(result) = self.poll_filter(cx, filter, Some(deadline)) {
+                return Poll::Ready(result.map(Some));
+            }
+            if Instant::now() >= deadline {
+                return Poll::Ready(Ok(None));
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Sends the queries with `writer` and waits at most `timeout` for their replies.
+    ///
+    /// This is the asynchronous equivalent of `Terminal::query`: the replies are fenced and
+    /// returned in the same way. Events which are not replies stay queued for the stream.
+    ///
+    /// The returned future is cancellation safe in that dropping it doesn't lose any events
+    /// besides replies to these queries. Replies which arrive after the future is dropped are
+    /// emitted by the stream if its filter accepts them.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use termina::{EventStream, PlatformTerminal, Query, QueryReply, Terminal as _};
+    /// # async fn f() -> std::io::Result<()> {
+    /// let mut terminal = PlatformTerminal::new()?;
+    /// terminal.enter_raw_mode()?;
+    /// let mut stream = EventStream::new(terminal.event_reader(), |event| !event.is_escape());
+    /// let replies = stream
+    ///     .query(&mut terminal, &[Query::CursorPosition], Duration::from_millis(100))
+    ///     .await?;
+    /// if let QueryReply::CursorPosition { line, col } = replies[0] {
+    ///     // ...
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn query<W: io::Write + ?Sized>(
+        &mut self,
+        writer: &mut W,
+        queries: &[Query],
+        timeout: Duration,
+    ) -> io::Result<Vec<QueryReply>> {
+        let deadline = Instant::now() + timeout;
+        query::write_requests(writer, queries)?;
+
+        let queries: Arc<[Query]> = queries.into();
+        let filter: Filter = {
+            let queries = queries.clone();
+            Arc::new(move |event| query::is_reply(&queries, event))
+        };
+        let mut replies = Replies::new(&self.reader, &queries);
+        let fenced = loop {
+            match self.read_until(&filter, deadline).await? {
+                Some(event) if replies.push(&queries, &event) => break true,
+                Some(_) => (),
+                None => break false,
+            }
+        };
+
+        Ok(replies.finish(fenced))
+    }
+
+    /// Writes `request` with `writer` and waits at most `timeout` for an event accepted by
+    /// `filter`, returning `None` on timeout.
+    ///
+    /// This is for requests which [Query] doesn't cover. Unlike [Self::query] the request is not
+    /// fenced so unsupported requests wait for the whole timeout. Like [Self::query] other events
+    /// stay queued for the stream.
+    pub async fn query_event<W, F>(
+        &mut self,
+        writer: &mut W,
+        request: impl fmt::Display,
+        filter: F,
+        timeout: Duration,
+    ) -> io::Result<Option<Event>>
+    where
+        W: io::Write + ?Sized,
+        F: Fn(&Event) -> bool + Send + Sync + 'static,
+    {
+        let deadline = Instant::now() + timeout;
+        write!(writer, "{request}")?;
+        writer.flush()?;
+        self.read_until(&(Arc::new(filter) as Filter), deadline)
+            .await
     }
 }
 
@@ -92,33 +361,205 @@ impl Stream for EventStream {
     type Item = io::Result<Event>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match self
-            .reader
-            .poll(Some(Duration::from_secs(0)), &*self.filter)
-        {
-            Ok(true) => match self.reader.read(&*self.filter) {
-                Ok(event) => Poll::Ready(Some(Ok(event))),
-                Err(err) => Poll::Ready(Some(Err(err))),
-            },
-            Ok(false) => {
-                if !self
-                    .stream_wake_task_executed
-                    .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
-                    .unwrap_or_else(|x| x)
-                {
-                    self.stream_wake_task_should_shutdown
-                        .store(false, Ordering::SeqCst);
-                    let _ = self.task_sender.send(Task {
-                        stream_waker: cx.waker().clone(),
-                        stream_wake_task_executed: self.stream_wake_task_executed.clone(),
-                        stream_wake_task_should_shutdown: self
-                            .stream_wake_task_should_shutdown
-                            .clone(),
-                    });
-                }
-                Poll::Pending
+        let this = self.get_mut();
+        let filter = this.filter.clone();
+        this.poll_filter(cx, &filter, None).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        future::Future,
+        sync::Arc,
+        task::{Wake, Waker},
+    };
+
+    use crate::{escape::csi, OneBased, Terminal as _, VirtualTerminal, WindowSize};
+
+    use super::*;
+
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<T>(future: impl Future<Output = T>) -> T {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
             }
-            Err(err) => Poll::Ready(Some(Err(err))),
         }
     }
+
+    fn next(stream: &mut EventStream) -> Option<io::Result<Event>> {
+        block_on(future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)))
+    }
+
+    #[test]
+    fn query() {
+        let mut terminal = VirtualTerminal::new(WindowSize::TEST);
+        let input = terminal.input();
+        let mut stream = EventStream::new(terminal.event_reader(), |_| true);
+
+        // A key press arrives before the replies and isn't consumed by the query.
+        input.send_bytes(b"q\x1b[12;5R\x1b[?62;22c");
+        let replies = block_on(stream.query(
+            &mut terminal,
+            &[Query::CursorPosition, Query::BackgroundColor],
+            Duration::from_secs(1),
+        ))
+        .unwrap();
+        assert_eq!(
+            replies,
+            [
+                QueryReply::CursorPosition {
+                    line: OneBased::new(12).unwrap(),
+                    col: OneBased::new(5).unwrap(),
+                },
+                QueryReply::Unsupported,
+            ]
+        );
+        assert!(matches!(next(&mut stream), Some(Ok(Event::Key(_)))));
+
+        // The terminal doesn't reply.
+        let reply = block_on(stream.query_event(
+            &mut terminal,
+            csi::Csi::Cursor(csi::Cursor::RequestActivePositionReport),
+            |event| matches!(event, Event::Csi(_)),
+            Duration::from_millis(20),
+        ))
+        .unwrap();
+        assert_eq!(reply, None);
+
+        // The helper thread polling for the reply is stopped for the stream.
+        input.send_bytes(b"x");
+        assert!(matches!(next(&mut stream), Some(Ok(Event::Key(_)))));
+    }
+
+    #[test]
+    fn shared_reader() {
+        let terminal = VirtualTerminal::new(WindowSize::TEST);
+        let input = terminal.input();
+        let mut keys = EventStream::new(terminal.event_reader(), |event| {
+            matches!(event, Event::Key(_))
+        });
+        let mut focus = EventStream::new(terminal.event_reader(), |event| {
+            matches!(event, Event::FocusIn)
+        });
+
+        // The key stream's helper thread blocks in the reader until it reads a key.
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let poll = Pin::new(&mut keys).poll_next(&mut Context::from_waker(&waker));
+        assert!(poll.is_pending());
+        thread::sleep(Duration::from_millis(20));
+
+        // The focus event is read by the key stream's helper thread and handed over.
+        input.send_bytes(b"\x1b[I");
+        let filter = focus.filter.clone();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert_eq!(
+            block_on(focus.read_until(&filter, deadline)).unwrap(),
+            Some(Event::FocusIn)
+        );
+        input.send_bytes(b"q");
+        assert!(matches!(next(&mut keys), Some(Ok(Event::Key(_)))));
+    }
+
+    #[cfg(all(unix, any(feature = "tokio", feature = "async-io")))]
+    type NewStream = fn(EventReader, fn(&Event) -> bool) -> EventStream;
+
+    /// Reads through streams which share the reader of a PTY, checking that they are driven by
+    /// the expected reactor and that input, timers and user events wake them.
+    #[cfg(all(unix, any(feature = "tokio", feature = "async-io")))]
+    async fn read_pty(new: NewStream, is_driver: fn(&Driver) -> bool) {
+        use std::io::Write as _;
+
+        use crate::{event::Token, terminal::UnixTerminal};
+
+        let size = WindowSize::TEST;
+        let (mut master, subsidiary) = crate::pty::open(size).unwrap();
+        let mut terminal =
+            UnixTerminal::from_fds(subsidiary.try_clone().unwrap(), subsidiary).unwrap();
+        terminal.enter_raw_mode().unwrap();
+        let reader = terminal.event_reader();
+        let mut keys = new(reader.clone(), |event| matches!(event, Event::Key(_)));
+        let mut others = new(reader.clone(), |event| {
+            matches!(event, Event::FocusIn | Event::Timer(_) | Event::User(_))
+        });
+        let deadline = || Instant::now() + Duration::from_secs(5);
+
+        // The key stream reads the focus event and queues it for the other stream.
+        master.write_all(b"\x1b[Iq").unwrap();
+        let is_key = keys.filter.clone();
+        let event = keys.read_until(&is_key, deadline()).await.unwrap();
+        assert!(matches!(event, Some(Event::Key(_))));
+        assert!(is_driver(&keys.driver));
+        let is_other = others.filter.clone();
+        let event = others.read_until(&is_other, deadline()).await.unwrap();
+        assert_eq!(event, Some(Event::FocusIn));
+        assert!(is_driver(&others.driver));
+
+        reader.add_timer(Instant::now() + Duration::from_millis(20), Token(1));
+        let event = others.read_until(&is_other, deadline()).await.unwrap();
+        assert_eq!(event, Some(Event::Timer(Token(1))));
+
+        let sender = reader.user_event_sender();
+        thread::spawn(move || sender.send(42u32));
+        let event = others.read_until(&is_other, deadline()).await.unwrap();
+        assert!(matches!(event, Some(Event::User(_))));
+
+        // Input which arrives while the stream waits wakes it.
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            master.write_all(b"x").unwrap();
+            master
+        });
+        let event = keys.read_until(&is_key, deadline()).await.unwrap();
+        assert!(matches!(event, Some(Event::Key(_))));
+        let _master = writer.join().unwrap();
+
+        let timeout = Instant::now() + Duration::from_millis(20);
+        assert_eq!(keys.read_until(&is_key, timeout).await.unwrap(), None);
+    }
+
+    #[cfg(all(unix, feature = "tokio"))]
+    #[test]
+    fn tokio_reactor() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(read_pty(EventStream::with_tokio, |driver| {
+                matches!(driver, Driver::Tokio(_))
+            }));
+    }
+
+    /// Streams created with `EventStream::new` don't use the tokio reactor, so a runtime without
+    /// IO and time doesn't make them panic.
+    #[cfg(all(unix, feature = "tokio"))]
+    #[test]
+    fn tokio_without_io() {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(read_pty(EventStream::new, |driver| {
+                !matches!(driver, Driver::Tokio(_))
+            }));
+    }
+
+    #[cfg(all(unix, feature = "async-io"))]
+    #[test]
+    fn async_io_reactor() {
+        async_io::block_on(read_pty(EventStream::new, |driver| {
+            matches!(driver, Driver::AsyncIo(_))
+        }));
+    }
 }
@@ -0,0 +1,183 @@
+// Driving an `EventStream` with an async runtime's reactor: the FDs which the event source polls
+// are registered with the reactor and the stream reads from the source without blocking once
+// any of them is readable, so no helper thread is needed.
+
+use std::{
+    future::Future,
+    io,
+    os::fd::OwnedFd,
+    pin::Pin,
+    sync::Arc,
+    task::{self, Context, Poll},
+    time::Instant,
+};
+
+use parking_lot::Mutex;
+
+use crate::event::{
+    reader::{EventReader, Inline},
+    Event,
+};
+
+use super::Filter;
+
+/// An async runtime's reactor.
+pub(crate) trait Backend {
+    type Fd;
+    type Timer;
+
+    fn register(fd: OwnedFd) -> io::Result<Self::Fd>;
+
+    /// Polls whether `fd` became readable since the last time this returned `Ready`.
+    fn poll_readable(fd: &Self::Fd, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    fn timer(deadline: Instant) -> Self::Timer;
+
+    fn poll_timer(timer: &mut Self::Timer, cx: &mut Context<'_>) -> Poll<()>;
+}
+
+pub(crate) struct Reactor<B: Backend> {
+    /// The generation of `fds`, see `EventSource::fds`. The FDs are read on the first poll.
+    generation: Option<u64>,
+    fds: Vec<B::Fd>,
+    /// The earliest of the reader's timers and the stream's deadline.
+    timer: Option<(Instant, B::Timer)>,
+    /// Woken by the reader when another caller queues an event or changes the FDs or timers.
+    waker: Arc<Mutex<Option<task::Waker>>>,
+}
+
+impl<B: Backend> Reactor<B> {
+    /// Returns `None` if the source can't be polled through FDs, in which case the stream falls
+    /// back to a helper thread.
+    pub(crate) fn new(reader: &EventReader) -> Option<Self> {
+        if !reader.has_fds() {
+            return None;
+        }
+        let waker = Arc::new(Mutex::new(None));
+        reader.add_stream_waker(&waker);
+        Some(Self {
+            generation: None,
+            fds: Vec::new(),
+            timer: None,
+            waker,
+        })
+    }
+
+    pub(crate) fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+        reader: &EventReader,
+        filter: &Filter,
+        deadline: Option<Instant>,
+    ) -> Poll<io::Result<Event>> {
+        *self.waker.lock() = Some(cx.waker().clone());
+        loop {
+            // Poll the FDs and the timer before reading so that anything which happens after the
+            // read wakes the task. Anything which was ready is polled again once the read has
+            // consumed it, which registers the task with the reactor.
+            let mut ready = false;
+            for fd in &self.fds {
+                match B::poll_readable(fd, cx) {
+                    Poll::Ready(Ok(())) => ready = true,
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => (),
+                }
+            }
+            if let Some((_, timer)) = &mut self.timer {
+                if B::poll_timer(timer, cx).is_ready() {
+                    self.timer = None;
+                    ready = true;
+                }
+            }
+
+            let (timer, fds) = match reader.poll_inline(&**filter, self.generation) {
+                Ok(Inline::Event(event)) => return Poll::Ready(Ok(event)),
+                Ok(Inline::Locked) => return Poll::Pending,
+                Ok(Inline::NotReady { timer, fds }) => (timer, fds),
+                Err(err) => return Poll::Ready(Err(err)),
+            };
+            if let Some((generation, fds)) = fds {
+                match fds.into_iter().map(B::register).collect() {
+                    Ok(fds) => self.fds = fds,
+                    Err(err) => return Poll::Ready(Err(err)),
+                }
+                self.generation = Some(generation);
+                ready = true;
+            }
+
+            let now = Instant::now();
+            if timer.is_some_and(|timer| timer <= now) {
+                // The timer expired after the read. It is emitted by the next read.
+                continue;
+            }
+            // A deadline which has passed is handled by the caller.
+            let wake_at = timer.into_iter().chain(deadline.filter(|d| *d > now)).min();
+            if wake_at != self.timer.as_ref().map(|(deadline, _)| *deadline) {
+                self.timer = wake_at.map(|deadline| (deadline, B::timer(deadline)));
+                ready = true;
+            }
+
+            if !ready {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub(crate) struct Tokio;
+
+#[cfg(feature = "tokio")]
+impl Backend for Tokio {
+    type Fd = tokio::io::unix::AsyncFd<OwnedFd>;
+    type Timer = Pin<Box<tokio::time::Sleep>>;
+
+    fn register(fd: OwnedFd) -> io::Result<Self::Fd> {
+        // Newer tokio deprecates this in favour of an `unsafe` constructor because it can't tell
+        // whether `T` owns the FD. An `OwnedFd` does, so it can't be closed while registered.
+        #[allow(deprecated)]
+        tokio::io::unix::AsyncFd::with_interest(fd, tokio::io::Interest::READABLE)
+    }
+
+    fn poll_readable(fd: &Self::Fd, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // The stream reads through the event source rather than the guard, so clear the
+        // readiness right away. Input which arrives after this sets it again.
+        fd.poll_read_ready(cx)
+            .map_ok(|mut guard| guard.clear_ready())
+    }
+
+    fn timer(deadline: Instant) -> Self::Timer {
+        Box::pin(tokio::time::sleep_until(deadline.into()))
+    }
+
+    fn poll_timer(timer: &mut Self::Timer, cx: &mut Context<'_>) -> Poll<()> {
+        timer.as_mut().poll(cx)
+    }
+}
+
+#[cfg(feature = "async-io")]
+pub(crate) struct AsyncIo;
+
+#[cfg(feature = "async-io")]
+impl Backend for AsyncIo {
+    type Fd = async_io::Async<OwnedFd>;
+    type Timer = async_io::Timer;
+
+    fn register(fd: OwnedFd) -> io::Result<Self::Fd> {
+        // The FDs are only polled, never read through the `Async`. Setting `O_NONBLOCK` would
+        // change the terminal's input handle for every process sharing it.
+        async_io::Async::new_nonblocking(fd)
+    }
+
+    fn poll_readable(fd: &Self::Fd, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        fd.poll_readable(cx)
+    }
+
+    fn timer(deadline: Instant) -> Self::Timer {
+        async_io::Timer::at(deadline)
+    }
+
+    fn poll_timer(timer: &mut Self::Timer, cx: &mut Context<'_>) -> Poll<()> {
+        Pin::new(timer).poll(cx).map(drop)
+    }
+}
@@ -2,14 +2,24 @@ pub(crate) mod base64;
 pub mod escape;
 pub mod event;
 pub(crate) mod parse;
+#[cfg(unix)]
+pub mod pty;
+pub mod screen;
 pub mod style;
 mod terminal;
 
 use std::{fmt, num::NonZeroU16};
 
-pub use event::{reader::EventReader, Event};
+pub use event::{
+    reader::{Coalescing, EventReader, Lagged, Subscription, SubscriptionOptions, UserEventSender},
+    source::VirtualInput,
+    Event,
+};
 pub use parse::Parser;
-pub use terminal::{PlatformHandle, PlatformTerminal, Terminal};
+pub use terminal::{
+    Capabilities, PlatformHandle, PlatformTerminal, Query, QueryReply, Session, SessionBuilder,
+    SynchronizedUpdate, SynchronizedWriter, Terminal, TerminalMode, VirtualTerminal,
+};
 
 #[cfg(feature = "event-stream")]
 pub use event::stream::EventStream;
@@ -64,7 +74,8 @@ impl From<NonZeroU16> for OneBased {
 /// The dimensions of a terminal screen.
 ///
 /// For both Unix and Windows, Termina returns the rows and columns.
-/// Pixel width and height are not supported on Windows.
+/// Pixel width and height are not supported on Windows. Many Unix terminals don't report them
+/// either: see `Terminal::query_dimensions` for asking the terminal directly.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct WindowSize {
     /// The width - the number of columns.
@@ -73,8 +84,19 @@ pub struct WindowSize {
     /// The height - the number of rows.
     #[doc(alias = "height")]
     pub rows: u16,
-    /// The height of the window in pixels.
+    /// The width of the window in pixels or `None` if it is unknown.
     pub pixel_width: Option<u16>,
-    /// The width of the window in pixels.
+    /// The height of the window in pixels or `None` if it is unknown.
     pub pixel_height: Option<u16>,
 }
+
+#[cfg(test)]
+impl WindowSize {
+    /// An 80x24 window with an unknown pixel size, for tests.
+    pub(crate) const TEST: Self = Self {
+        cols: 80,
+        rows: 24,
+        pixel_width: None,
+        pixel_height: None,
+    };
+}
@@ -13,15 +13,15 @@ use std::{collections::VecDeque, num::NonZeroU16, str};
 
 use crate::{
     escape::{
-        self,
+        self, apc,
         csi::{self, Csi, KittyKeyboardFlags, ThemeMode},
-        dcs,
+        dcs, osc,
     },
     event::{
         KeyCode, KeyEvent, KeyEventKind, KeyEventState, MediaKeyCode, ModifierKeyCode, Modifiers,
         MouseButton, MouseEvent, MouseEventKind,
     },
-    style, Event,
+    style, Event, WindowSize,
 };
 
 /// A parser for ANSI escape sequences.
@@ -58,19 +58,36 @@ impl Parser {
 
     fn process_bytes(&mut self, maybe_more: bool) {
         let mut start = 0;
-        for n in 0..self.buffer.len() {
-            let end = n + 1;
-            match parse_event(
-                &self.buffer[start..end],
-                maybe_more || end < self.buffer.len(),
-            ) {
-                Ok(Some(event)) => {
-                    self.events.push_back(event);
-                    start = end;
+        loop {
+            let resume = start;
+            for n in resume..self.buffer.len() {
+                let end = n + 1;
+                match parse_event(
+                    &self.buffer[start..end],
+                    maybe_more || end < self.buffer.len(),
+                ) {
+                    Ok(Some(event)) => {
+                        self.events.push_back(event);
+                        start = end;
+                    }
+                    Ok(None) => continue,
+                    Err(_) => start = end,
                 }
-                Ok(None) => continue,
-                Err(_) => start = end,
             }
+            // Without more input an unterminated APC or OSC is Alt+_ or Alt+] rather than the
+            // start of a sequence. Like a lone Escape, treat it as a key and parse the rest of the
+            // buffer as input.
+            let [b'\x1B', byte @ (b'_' | b']'), ..] = self.buffer[start..] else {
+                break;
+            };
+            if maybe_more {
+                break;
+            }
+            self.events.push_back(Event::Key(KeyEvent::new(
+                KeyCode::Char(byte as char),
+                Modifiers::ALT,
+            )));
+            start += 2;
         }
         self.advance(start);
     }
@@ -94,7 +111,7 @@ impl Parser {
 mod windows {
     use windows_sys::Win32::System::Console;
 
-    use crate::{OneBased, WindowSize};
+    use crate::OneBased;
 
     use super::*;
 
@@ -198,6 +215,8 @@ fn parse_event(buffer: &[u8], maybe_more: bool) -> Result<Option<Event>> {
                     }
                     b'[' => parse_csi(buffer),
                     b'P' => parse_dcs(buffer),
+                    b'_' => parse_apc(buffer),
+                    b']' => parse_osc(buffer),
                     b'\x1B' => Ok(Some(Event::Key(KeyCode::Escape.into()))),
                     _ => parse_event(&buffer[1..], maybe_more).map(|event_option| {
                         event_option.map(|event| {
@@ -311,7 +330,7 @@ fn parse_csi(buffer: &[u8]) -> Result<Option<Event>> {
             b'u' => return parse_csi_keyboard_enhancement_flags(buffer),
             b'c' => return parse_csi_primary_device_attributes(buffer),
             b'n' => return parse_csi_theme_mode(buffer),
-            b'y' => return parse_csi_synchronized_output_mode(buffer),
+            b'y' => return parse_csi_dec_private_mode_report(buffer),
             _ => None,
         },
         b'0'..=b'9' => {
@@ -333,6 +352,7 @@ fn parse_csi(buffer: &[u8]) -> Result<Option<Event>> {
                         b'~' => return parse_csi_special_key_code(buffer),
                         b'u' => return parse_csi_u_encoded_key_code(buffer),
                         b'R' => return parse_csi_cursor_position(buffer),
+                        b't' => return parse_csi_window_report(buffer),
                         _ => return parse_csi_modifier_key_code(buffer),
                     }
                 }
@@ -905,6 +925,45 @@ fn parse_csi_cursor_position(buffer: &[u8]) -> Result<Option<Event>> {
     ))))
 }
 
+fn parse_csi_window_report(buffer: &[u8]) -> Result<Option<Event>> {
+    // CSI 4 ; height ; width t
+    //   The reply to `Window::ReportTextAreaSizePixels`.
+    // CSI 6 ; height ; width t
+    //   The reply to `Window::ReportCellSizePixels`.
+    // CSI 48 ; rows ; cols ; height ; width t
+    //   An in-band resize notification, sent while `DecPrivateModeCode::InBandResize` is
+    //   enabled. The height and width are in pixels.
+    assert!(buffer.starts_with(b"\x1B[")); // CSI
+    assert!(buffer.ends_with(b"t"));
+
+    let s = str::from_utf8(&buffer[2..buffer.len() - 1])?;
+
+    let mut split = s.split(';');
+
+    let kind = next_parsed::<u8>(&mut split)?;
+    if kind == 48 {
+        let rows = next_parsed::<u16>(&mut split)?;
+        let cols = next_parsed::<u16>(&mut split)?;
+        let pixel_height = next_parsed::<u16>(&mut split)?;
+        let pixel_width = next_parsed::<u16>(&mut split)?;
+        return Ok(Some(Event::WindowResized(WindowSize {
+            cols,
+            rows,
+            pixel_width: (pixel_width != 0).then_some(pixel_width),
+            pixel_height: (pixel_height != 0).then_some(pixel_height),
+        })));
+    }
+    let height = Some(next_parsed::<i64>(&mut split)?);
+    let width = Some(next_parsed::<i64>(&mut split)?);
+
+    let window = match kind {
+        4 => csi::Window::ReportTextAreaSizePixelsResponse { width, height },
+        6 => csi::Window::ReportCellSizePixelsResponse { width, height },
+        _ => bail!(),
+    };
+    Ok(Some(Event::Csi(Csi::Window(Box::new(window)))))
+}
+
 fn parse_csi_keyboard_enhancement_flags(buffer: &[u8]) -> Result<Option<Event>> {
     // CSI ? flags u
     assert!(buffer.starts_with(b"\x1B[?")); // ESC [ ?
@@ -944,12 +1003,27 @@ fn parse_csi_primary_device_attributes(buffer: &[u8]) -> Result<Option<Event>> {
     assert!(buffer.starts_with(b"\x1B[?"));
     assert!(buffer.ends_with(b"c"));
 
-    // This is a stub for parsing the primary device attributes. This response is not
-    // exposed in the crossterm API so we don't need to parse the individual attributes yet.
     // See <https://vt100.net/docs/vt510-rm/DA1.html>
+    let s = str::from_utf8(&buffer[3..buffer.len() - 1])?;
+
+    let mut split = s.split(';');
+
+    let conformance_level = next_parsed::<u16>(&mut split)?;
+    let mut attributes = csi::DeviceAttributeFlags::empty();
+    for attribute in split {
+        let attribute = attribute
+            .parse::<u16>()
+            .map_err(|_| MalformedSequenceError)?;
+        if attribute < u64::BITS as u16 {
+            attributes |= csi::DeviceAttributeFlags::from_bits_retain(1 << attribute);
+        }
+    }
 
     Ok(Some(Event::Csi(Csi::Device(
-        csi::Device::DeviceAttributes(()),
+        csi::Device::DeviceAttributes(csi::DeviceAttributes {
+            conformance_level,
+            attributes,
+        }),
     ))))
 }
 
@@ -978,8 +1052,8 @@ fn parse_csi_theme_mode(buffer: &[u8]) -> Result<Option<Event>> {
     )))))
 }
 
-fn parse_csi_synchronized_output_mode(buffer: &[u8]) -> Result<Option<Event>> {
-    // CSI ? 2026 ; 0 $ y
+fn parse_csi_dec_private_mode_report(buffer: &[u8]) -> Result<Option<Event>> {
+    // DECRPM: CSI ? Pd ; Ps $ y
     assert!(buffer.starts_with(b"\x1B[?"));
     assert!(buffer.ends_with(b"y"));
 
@@ -991,25 +1065,25 @@ fn parse_csi_synchronized_output_mode(buffer: &[u8]) -> Result<Option<Event>> {
 
     let mut split = s.split(';');
 
-    let mode = csi::DecPrivateModeCode::SynchronizedOutput;
-    if next_parsed::<u16>(&mut split)? != mode as u16 {
-        bail!();
-    }
+    let mode = csi::DecPrivateMode::from(next_parsed::<u16>(&mut split)?);
 
-    // For synchronized output specifically, 3 is undefined and 0 and 4 are treated as "not
-    // supported."
-    let setting = match next_parsed::<u8>(&mut split)? {
-        0 | 4 => csi::DecModeSetting::NotRecognized,
-        1 => csi::DecModeSetting::Set,
-        2 => csi::DecModeSetting::Reset,
+    let setting = match (mode, next_parsed::<u8>(&mut split)?) {
+        // For synchronized output specifically, 3 is undefined and 0 and 4 are treated as "not
+        // supported."
+        (csi::DecPrivateMode::Code(csi::DecPrivateModeCode::SynchronizedOutput), 0 | 4) => {
+            csi::DecModeSetting::NotRecognized
+        }
+        (csi::DecPrivateMode::Code(csi::DecPrivateModeCode::SynchronizedOutput), 3) => bail!(),
+        (_, 0) => csi::DecModeSetting::NotRecognized,
+        (_, 1) => csi::DecModeSetting::Set,
+        (_, 2) => csi::DecModeSetting::Reset,
+        (_, 3) => csi::DecModeSetting::PermanentlySet,
+        (_, 4) => csi::DecModeSetting::PermanentlyReset,
         _ => bail!(),
     };
 
     Ok(Some(Event::Csi(Csi::Mode(
-        csi::Mode::ReportDecPrivateMode {
-            mode: csi::DecPrivateMode::Code(mode),
-            setting,
-        },
+        csi::Mode::ReportDecPrivateMode { mode, setting },
     ))))
 }
 
@@ -1018,21 +1092,46 @@ fn parse_dcs(buffer: &[u8]) -> Result<Option<Event>> {
     if !buffer.ends_with(escape::ST.as_bytes()) {
         return Ok(None);
     }
+    // XTVERSION: DCS > | text ST
+    if let Some(text) = buffer[..buffer.len() - 2].strip_prefix(b"\x1bP>|") {
+        return Ok(Some(Event::Dcs(dcs::Dcs::TerminalNameAndVersion(
+            str::from_utf8(text)?.to_string(),
+        ))));
+    }
+    // XTGETTCAP response: DCS Ps + r Pt ST
+    if buffer.get(3..5) == Some(b"+r") {
+        let is_request_valid = match buffer[2] {
+            b'1' => true,
+            b'0' => false,
+            _ => bail!(),
+        };
+        let payload = &buffer[5..buffer.len() - 2];
+        let (name, value) = match payload.iter().position(|&b| b == b'=') {
+            Some(i) => (&payload[..i], Some(parse_hex_string(&payload[i + 1..])?)),
+            None => (payload, None),
+        };
+        return Ok(Some(Event::Dcs(dcs::Dcs::TerminfoCapability {
+            is_request_valid,
+            name: parse_hex_string(name)?,
+            value,
+        })));
+    }
+    // DECRPCRA: DCS Pid ! ~ D...D ST
+    if let Some((request_id, checksum)) =
+        str::from_utf8(&buffer[2..buffer.len() - 2])?.split_once("!~")
+    {
+        let request_id = request_id.parse().map_err(|_| MalformedSequenceError)?;
+        let checksum = u16::from_str_radix(checksum, 16).map_err(|_| MalformedSequenceError)?;
+        return Ok(Some(Event::Dcs(dcs::Dcs::RectangularAreaChecksum {
+            request_id,
+            checksum,
+        })));
+    }
     match buffer[buffer.len() - 3] {
         // SGR response: DCS Ps $ r SGR m ST
         b'm' => {
-            if buffer.get(3..5) != Some(b"$r") {
-                bail!();
-            }
-            // NOTE: <https://www.xfree86.org/current/ctlseqs.html> says that '1' is a valid
-            // request and '0' is invalid while the vt100.net docs for DECRQSS say the opposite.
-            // Kitty and WezTerm both follow the ctlseqs doc.
-            let is_request_valid = match buffer[2] {
-                b'1' => true,
-                // TODO: don't parse attributes if the request isn't valid?
-                b'0' => false,
-                _ => bail!(),
-            };
+            // TODO: don't parse attributes if the request isn't valid?
+            let is_request_valid = parse_decrpss_validity(buffer)?;
             let s = str::from_utf8(&buffer[5..buffer.len() - 3])?;
             let mut sgrs = Vec::new();
             // TODO: is this correct? What about terminals that use ';' for true colors?
@@ -1044,6 +1143,129 @@ fn parse_dcs(buffer: &[u8]) -> Result<Option<Event>> {
                 value: dcs::DcsResponse::GraphicRendition(sgrs),
             })))
         }
+        // DECSSDT response: DCS Ps $ r Pn $ ~ ST
+        // DECSASD response: DCS Ps $ r Pn $ } ST
+        final_byte @ (b'~' | b'}') => {
+            let is_request_valid = parse_decrpss_validity(buffer)?;
+            let s = str::from_utf8(&buffer[5..buffer.len() - 3])?;
+            let Some(s) = s.strip_suffix('$') else {
+                bail!();
+            };
+            // An empty parameter is the default: zero.
+            let n = if s.is_empty() {
+                0
+            } else {
+                s.parse::<u8>().map_err(|_| MalformedSequenceError)?
+            };
+            let value = if final_byte == b'~' {
+                dcs::DcsResponse::StatusLineType(match n {
+                    0 => csi::StatusLineType::None,
+                    1 => csi::StatusLineType::Indicator,
+                    2 => csi::StatusLineType::HostWritable,
+                    _ => bail!(),
+                })
+            } else {
+                dcs::DcsResponse::ActiveStatusDisplay(match n {
+                    0 => csi::ActiveStatusDisplay::Main,
+                    1 => csi::ActiveStatusDisplay::StatusLine,
+                    _ => bail!(),
+                })
+            };
+            Ok(Some(Event::Dcs(dcs::Dcs::Response {
+                is_request_valid,
+                value,
+            })))
+        }
+        _ => bail!(),
+    }
+}
+
+fn parse_apc(buffer: &[u8]) -> Result<Option<Event>> {
+    assert!(buffer.starts_with(escape::APC.as_bytes()));
+    if !buffer.ends_with(escape::ST.as_bytes()) {
+        return Ok(None);
+    }
+    let s = str::from_utf8(&buffer[2..buffer.len() - 2])?;
+    // Kitty graphics protocol: APC G control-data ; payload ST
+    let Some(s) = s.strip_prefix('G') else {
+        bail!();
+    };
+    let (control_data, payload) = s.split_once(';').unwrap_or((s, ""));
+    Ok(Some(Event::Apc(apc::Apc::KittyGraphics {
+        control_data: control_data.to_string(),
+        payload: payload.to_string(),
+    })))
+}
+
+fn parse_osc(buffer: &[u8]) -> Result<Option<Event>> {
+    assert!(buffer.starts_with(escape::OSC.as_bytes()));
+    // OSC replies may be terminated by either ST or BEL.
+    let Some(content) = buffer
+        .strip_suffix(escape::ST.as_bytes())
+        .or_else(|| buffer.strip_suffix(b"\x07"))
+    else {
+        return Ok(None);
+    };
+    let s = str::from_utf8(&content[2..])?;
+    let Some((command, value)) = s.split_once(';') else {
+        bail!();
+    };
+    let osc = match command {
+        "10" => osc::Osc::ReportForegroundColor(parse_xcolor(value)?),
+        "11" => osc::Osc::ReportBackgroundColor(parse_xcolor(value)?),
+        _ => bail!(),
+    };
+    Ok(Some(Event::Osc(osc)))
+}
+
+/// Parses a color in the `rgb:R/G/B` form where each channel has one to four hex digits.
+fn parse_xcolor(s: &str) -> Result<style::RgbColor> {
+    let Some(s) = s.strip_prefix("rgb:") else {
+        bail!();
+    };
+    let mut channels = s.split('/').map(|channel| {
+        if channel.is_empty() || channel.len() > 4 {
+            bail!();
+        }
+        let value = u32::from_str_radix(channel, 16).map_err(|_| MalformedSequenceError)?;
+        let max = (1u32 << (4 * channel.len())) - 1;
+        Ok((value * 255 / max) as u8)
+    });
+    let mut next = || channels.next().unwrap_or(Err(MalformedSequenceError));
+    Ok(style::RgbColor::new(next()?, next()?, next()?))
+}
+
+fn parse_hex_string(s: &[u8]) -> Result<String> {
+    if s.len() % 2 != 0 {
+        bail!();
+    }
+    let bytes = s
+        .chunks_exact(2)
+        .map(|pair| Ok(hex_digit(pair[0])? << 4 | hex_digit(pair[1])?))
+        .collect::<Result<Vec<_>>>()?;
+    String::from_utf8(bytes).map_err(|_| MalformedSequenceError)
+}
+
+fn hex_digit(byte: u8) -> Result<u8> {
+    match byte {
+        b'0'..=b'9' => Ok(byte - b'0'),
+        b'a'..=b'f' => Ok(byte - b'a' + 10),
+        b'A'..=b'F' => Ok(byte - b'A' + 10),
+        _ => bail!(),
+    }
+}
+
+/// Parses the `Ps` part of a DECRPSS response: `DCS Ps $ r D...D ST`.
+fn parse_decrpss_validity(buffer: &[u8]) -> Result<bool> {
+    if buffer.get(3..5) != Some(b"$r") {
+        bail!();
+    }
+    // NOTE: <https://www.xfree86.org/current/ctlseqs.html> says that '1' is a valid
+    // request and '0' is invalid while the vt100.net docs for DECRQSS say the opposite.
+    // Kitty and WezTerm both follow the ctlseqs doc.
+    match buffer[2] {
+        b'1' => Ok(true),
+        b'0' => Ok(false),
         _ => bail!(),
     }
 }
@@ -1060,7 +1282,7 @@ fn parse_sgr(buffer: &str) -> Result<csi::Sgr> {
         "24" => Sgr::Underline(Underline::None),
         "4" => Sgr::Underline(Underline::Single),
         "21" => Sgr::Underline(Underline::Double),
-        "4:3 " => Sgr::Underline(Underline::Curly),
+        "4:3" => Sgr::Underline(Underline::Curly),
         "4:4" => Sgr::Underline(Underline::Dotted),
         "4:5" => Sgr::Underline(Underline::Dashed),
         "25" => Sgr::Blink(Blink::None),
@@ -1183,4 +1405,193 @@ mod test {
             })
         );
     }
+    #[test]
+    fn parse_capability_replies() {
+        let event = parse_event(b"\x1b[?65;4;22;52c", false).unwrap().unwrap();
+        assert_eq!(
+            event,
+            Event::Csi(Csi::Device(csi::Device::DeviceAttributes(
+                csi::DeviceAttributes {
+                    conformance_level: 65,
+                    attributes: csi::DeviceAttributeFlags::SIXEL_GRAPHICS
+                        | csi::DeviceAttributeFlags::ANSI_COLOR
+                        | csi::DeviceAttributeFlags::CLIPBOARD_ACCESS,
+                }
+            )))
+        );
+        let event = parse_event(b"\x1b[?2048;2$y", false).unwrap().unwrap();
+        assert_eq!(
+            event,
+            Event::Csi(Csi::Mode(csi::Mode::ReportDecPrivateMode {
+                mode: csi::DecPrivateMode::Code(csi::DecPrivateModeCode::InBandResize),
+                setting: csi::DecModeSetting::Reset,
+            }))
+        );
+        let event = parse_event(b"\x1bP>|kitty(0.36.4)\x1b\\", false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event,
+            Event::Dcs(dcs::Dcs::TerminalNameAndVersion(
+                "kitty(0.36.4)".to_string()
+            ))
+        );
+        let event = parse_event(b"\x1b_Gi=31;OK\x1b\\", false).unwrap().unwrap();
+        assert_eq!(
+            event,
+            Event::Apc(apc::Apc::KittyGraphics {
+                control_data: "i=31".to_string(),
+                payload: "OK".to_string(),
+            })
+        );
+    }
+    #[test]
+    fn parse_query_replies() {
+        let event = parse_event(b"\x1b]11;rgb:1e1e/2f2f/ffff\x07", false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event,
+            Event::Osc(osc::Osc::ReportBackgroundColor(style::RgbColor::new(
+                0x1e, 0x2f, 0xff
+            )))
+        );
+        let event = parse_event(b"\x1b]10;rgb:f/0/8\x1b\\", false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event,
+            Event::Osc(osc::Osc::ReportForegroundColor(style::RgbColor::new(
+                0xff, 0, 0x88
+            )))
+        );
+        let event = parse_event(b"\x1b[48;24;80;480;800t", false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event,
+            Event::WindowResized(WindowSize {
+                cols: 80,
+                rows: 24,
+                pixel_width: Some(800),
+                pixel_height: Some(480),
+            })
+        );
+        let event = parse_event(b"\x1b[4;480;800t", false).unwrap().unwrap();
+        assert_eq!(
+            event,
+            Event::Csi(Csi::Window(Box::new(
+                csi::Window::ReportTextAreaSizePixelsResponse {
+                    width: Some(800),
+                    height: Some(480),
+                }
+            )))
+        );
+        let event = parse_event(b"\x1b[6;20;10t", false).unwrap().unwrap();
+        assert_eq!(
+            event,
+            Event::Csi(Csi::Window(Box::new(
+                csi::Window::ReportCellSizePixelsResponse {
+                    width: Some(10),
+                    height: Some(20),
+                }
+            )))
+        );
+        let event = parse_event(b"\x1bP1+r536D756C78=1B5B34", false).unwrap();
+        assert_eq!(event, None);
+        // Non-ASCII or signed "hex" digits are malformed rather than a panic.
+        assert!(parse_event(b"\x1bP1+ra\xc3\xa91\x1b\\", false).is_err());
+        assert!(parse_event(b"\x1bP1+r+1+1\x1b\\", false).is_err());
+        let event = parse_event(b"\x1bP1+r544E=787465726D\x1b\\", false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event,
+            Event::Dcs(dcs::Dcs::TerminfoCapability {
+                is_request_valid: true,
+                name: "TN".to_string(),
+                value: Some("xterm".to_string()),
+            })
+        );
+    }
+    #[test]
+    fn parse_dcs_rectangular_area_checksum() {
+        let event = parse_event(b"\x1bP12!~E0F3\x1b\\", false).unwrap().unwrap();
+        assert_eq!(
+            event,
+            Event::Dcs(dcs::Dcs::RectangularAreaChecksum {
+                request_id: 12,
+                checksum: 0xe0f3,
+            })
+        );
+    }
+    #[test]
+    fn parse_dcs_status_display_responses() {
+        let event = parse_event(b"\x1bP1$r2$~\x1b\\", false).unwrap().unwrap();
+        assert_eq!(
+            event,
+            Event::Dcs(dcs::Dcs::Response {
+                is_request_valid: true,
+                value: dcs::DcsResponse::StatusLineType(csi::StatusLineType::HostWritable),
+            })
+        );
+        let event = parse_event(b"\x1bP1$r0$}\x1b\\", false).unwrap().unwrap();
+        assert_eq!(
+            event,
+            Event::Dcs(dcs::Dcs::Response {
+                is_request_valid: true,
+                value: dcs::DcsResponse::ActiveStatusDisplay(csi::ActiveStatusDisplay::Main),
+            })
+        );
+    }
+    #[test]
+    fn parse_alt_underscore() {
+        let mut parser = Parser::default();
+        parser.parse(b"\x1b_", false);
+        assert_eq!(
+            parser.pop(),
+            Some(Event::Key(KeyEvent::new(
+                KeyCode::Char('_'),
+                Modifiers::ALT
+            )))
+        );
+        assert_eq!(parser.pop(), None);
+
+        // Input after the key isn't held back waiting for a string terminator.
+        parser.parse(b"\x1b_a", false);
+        assert_eq!(
+            parser.pop(),
+            Some(Event::Key(KeyEvent::new(
+                KeyCode::Char('_'),
+                Modifiers::ALT
+            )))
+        );
+        assert_eq!(parser.pop(), Some(Event::Key(KeyCode::Char('a').into())));
+        assert_eq!(parser.pop(), None);
+
+        // A partial APC is kept while more input may follow.
+        parser.parse(b"\x1b_Gi=31;OK", true);
+        assert_eq!(parser.pop(), None);
+        parser.parse(b"\x1b\\", false);
+        assert!(matches!(parser.pop(), Some(Event::Apc(_))));
+    }
+    #[test]
+    fn parse_alt_right_bracket() {
+        let mut parser = Parser::default();
+        parser.parse(b"\x1b]", false);
+        assert_eq!(
+            parser.pop(),
+            Some(Event::Key(KeyEvent::new(
+                KeyCode::Char(']'),
+                Modifiers::ALT
+            )))
+        );
+        assert_eq!(parser.pop(), None);
+
+        // A partial OSC reply is kept while more input may follow.
+        parser.parse(b"\x1b]11;rgb:0/0/0", true);
+        assert_eq!(parser.pop(), None);
+        parser.parse(b"\x07", false);
+        assert!(matches!(parser.pop(), Some(Event::Osc(_))));
+    }
 }
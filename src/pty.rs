@@ -0,0 +1,283 @@
+// Hosting a terminal: opening a PTY pair and running a child process on the subsidiary side.
+// The setup in `spawn` follows what terminal emulators do before `exec`ing the shell, see for
+// example WezTerm's `portable-pty`:
+// <https://github.com/wezterm/wezterm/blob/a87358516004a652ad840bc1661bdf65ffc89b43/pty/src/unix.rs>
+
+use std::{
+    io,
+    os::{
+        fd::{AsFd, BorrowedFd, OwnedFd},
+        unix::process::CommandExt as _,
+    },
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use rustix::{
+    fs::{Mode, OFlags},
+    pty::OpenptFlags,
+    termios,
+};
+
+use crate::{
+    event::source::{self, PollTimeout},
+    WindowSize,
+};
+
+/// Opens a new PTY pair with the given size.
+///
+/// Returns the controlling side of the PTY and the subsidiary side, which is the terminal device
+/// that a child process reads from and writes to. The subsidiary can be passed to
+/// `UnixTerminal::from_fds` to drive it as a terminal.
+pub fn open(size: WindowSize) -> io::Result<(PtyMaster, OwnedFd)> {
+    // Otherwise a spawned child would inherit the master and keep the PTY open, so the child
+    // wouldn't receive SIGHUP when the `PtyMaster` is dropped.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "netbsd"
+    ))]
+    let master =
+        rustix::pty::openpt(OpenptFlags::RDWR | OpenptFlags::NOCTTY | OpenptFlags::CLOEXEC)?;
+    // Elsewhere a child spawned by another thread before the flag is set still inherits the
+    // master.
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "netbsd"
+    )))]
+    let master = {
+        let master = rustix::pty::openpt(OpenptFlags::RDWR | OpenptFlags::NOCTTY)?;
+        rustix::io::fcntl_setfd(&master, rustix::io::FdFlags::CLOEXEC)?;
+        master
+    };
+    rustix::pty::grantpt(&master)?;
+    rustix::pty::unlockpt(&master)?;
+    let name = rustix::pty::ptsname(&master, Vec::new())?;
+    // Without `NOCTTY` opening the subsidiary would make it the controlling terminal of this
+    // process if it didn't have one.
+    let subsidiary = rustix::fs::open(
+        name.as_c_str(),
+        OFlags::RDWR | OFlags::NOCTTY | OFlags::CLOEXEC,
+        Mode::empty(),
+    )?;
+    let master = PtyMaster { fd: master };
+    master.resize(size)?;
+    Ok((master, subsidiary))
+}
+
+/// Spawns `command` in a new PTY with the given size.
+///
+/// The child's stdin, stdout and stderr are the subsidiary side of the PTY, which is also the
+/// child's controlling terminal: the child is the leader of a new session, so it receives
+/// SIGWINCH when the PTY is resized with [PtyMaster::resize] and SIGHUP when the [PtyMaster] is
+/// dropped.
+///
+/// ```no_run
+/// # use std::{io::Write as _, process::Command, time::Duration};
+/// # use termina::{pty, screen::Screen, WindowSize};
+/// let size = WindowSize { cols: 80, rows: 24, pixel_width: None, pixel_height: None };
+/// let (mut master, mut child) = pty::spawn(&mut Command::new("vim"), size)?;
+/// let mut screen = Screen::new(size);
+/// let mut buf = [0; 4096];
+/// // Draw what vim displays until it is idle for a second.
+/// while let Some(read) = master.read_timeout(&mut buf, Some(Duration::from_secs(1)))? {
+///     if read == 0 {
+///         break;
+///     }
+///     screen.process(&buf[..read]);
+/// }
+/// println!("{}", screen.text());
+/// master.write_all(b":q!\r")?;
+/// child.wait()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn spawn(command: &mut Command, size: WindowSize) -> io::Result<(PtyMaster, Child)> {
+    let (master, subsidiary) = open(size)?;
+    command
+        .stdin(Stdio::from(subsidiary.try_clone()?))
+        .stdout(Stdio::from(subsidiary.try_clone()?))
+        .stderr(Stdio::from(subsidiary));
+    // SAFETY: the closure only makes system calls, which is safe between `fork` and `exec`.
+    unsafe {
+        command.pre_exec(|| {
+            rustix::process::setsid()?;
+            // stdin has been replaced with the subsidiary by now.
+            rustix::process::ioctl_tiocsctty(rustix::stdio::stdin())?;
+            Ok(())
+        });
+    }
+    let child = command.spawn()?;
+    Ok((master, child))
+}
+
+/// The controlling side of a PTY, created by [open] or [spawn].
+///
+/// Reading from the master reads what the child process wrote to the terminal and writing to it
+/// sends input to the child, so writing a key sequence simulates a key press. The output is
+/// what the child draws, which a [Screen](crate::screen::Screen) can process to test what the
+/// child displays.
+///
+/// Once the subsidiary side has been closed by every process, for example because the child
+/// exited, reads return 0 bytes.
+#[derive(Debug)]
+pub struct PtyMaster {
+    fd: OwnedFd,
+}
+
+impl PtyMaster {
+    /// Sets the size of the PTY. The foreground process group of the PTY receives SIGWINCH.
+    pub fn resize(&self, size: WindowSize) -> io::Result<()> {
+        let winsize = termios::Winsize {
+            ws_row: size.rows,
+            ws_col: size.cols,
+            ws_xpixel: size.pixel_width.unwrap_or(0),
+            ws_ypixel: size.pixel_height.unwrap_or(0),
+        };
+        termios::tcsetwinsize(&self.fd, winsize)?;
+        Ok(())
+    }
+
+    pub fn get_dimensions(&self) -> io::Result<WindowSize> {
+        Ok(termios::tcgetwinsize(&self.fd)?.into())
+    }
+
+    /// Reads the child's output into `buf`, waiting at most `timeout` for output.
+    ///
+    /// Returns `Ok(None)` if the timeout elapsed without output and `Ok(Some(0))` once the
+    /// subsidiary side has been closed.
+    pub fn read_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> io::Result<Option<usize>> {
+        let timeout = PollTimeout::new(timeout);
+        loop {
+            let ready = match source::poll(&[self.fd.as_fd()], timeout.leftover()) {
+                Ok(ready) => ready[0],
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            if ready {
+                match read(&self.fd, buf) {
+                    Ok(read) => return Ok(Some(read)),
+                    Err(rustix::io::Errno::INTR | rustix::io::Errno::AGAIN) => (),
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            if timeout.leftover().is_some_and(|t| t.is_zero()) {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Reads from the master. Reading from a hung up PTY fails with `EIO` rather than returning 0
+/// on some platforms, for example Linux, so that is reported as the end of the output.
+fn read(fd: &OwnedFd, buf: &mut [u8]) -> rustix::io::Result<usize> {
+    match rustix::io::read(fd, buf) {
+        Err(rustix::io::Errno::IO) => Ok(0),
+        result => result,
+    }
+}
+
+impl AsFd for PtyMaster {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl io::Read for PtyMaster {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(read(&self.fd, buf)?)
+    }
+}
+
+impl io::Write for PtyMaster {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(rustix::io::write(&self.fd, buf)?)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read as _;
+
+    use crate::{screen::Screen, OneBased};
+
+    use super::*;
+
+    #[test]
+    fn spawn_child() {
+        let (mut master, mut child) = spawn(
+            Command::new("sh").args(["-c", r"stty size; printf '\033[7mx'"]),
+            WindowSize::TEST,
+        )
+        .unwrap();
+
+        let mut screen = Screen::new(WindowSize::TEST);
+        let mut buf = [0; 1024];
+        loop {
+            let read = master
+                .read_timeout(&mut buf, Some(Duration::from_secs(5)))
+                .unwrap()
+                .expect("timed out");
+            if read == 0 {
+                break;
+            }
+            screen.process(&buf[..read]);
+        }
+        child.wait().unwrap();
+
+        let text = screen.text();
+        assert_eq!(text.lines().take(2).collect::<Vec<_>>(), ["24 80", "x"]);
+        let x = screen
+            .cell(OneBased::new(2).unwrap(), OneBased::new(1).unwrap())
+            .unwrap();
+        assert!(x.style.reverse);
+        assert_eq!(master.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn close_on_exec() {
+        let (master, subsidiary) = open(WindowSize::TEST).unwrap();
+        for fd in [master.as_fd(), subsidiary.as_fd()] {
+            let flags = rustix::io::fcntl_getfd(fd).unwrap();
+            assert!(flags.contains(rustix::io::FdFlags::CLOEXEC));
+        }
+    }
+
+    #[test]
+    fn read_after_hangup() {
+        let (mut master, subsidiary) = open(WindowSize::TEST).unwrap();
+        let mut buf = [0; 1024];
+        assert_eq!(
+            master.read_timeout(&mut buf, Some(Duration::ZERO)).unwrap(),
+            None
+        );
+
+        // Output written before the hangup is read before the end of the output.
+        let output = vec![b'a'; 2000];
+        rustix::io::write(&subsidiary, &output).unwrap();
+        drop(subsidiary);
+        let mut read = Vec::new();
+        loop {
+            let n = master
+                .read_timeout(&mut buf, Some(Duration::from_secs(5)))
+                .unwrap()
+                .expect("timed out");
+            if n == 0 {
+                break;
+            }
+            read.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(read, output);
+        assert_eq!(master.read_timeout(&mut buf, None).unwrap(), Some(0));
+    }
+}
@@ -0,0 +1,1675 @@
+//! A headless terminal screen for testing what an application draws.
+
+// CREDIT: the parser is a simplified version of Paul Williams' DEC compatible state machine:
+// <https://vt100.net/emu/dec_ansi_parser>. The behavior of the control functions follows xterm's
+// where the DEC manuals and ECMA-48 leave room for interpretation:
+// <https://invisible-island.net/xterm/ctlseqs/ctlseqs.html>
+
+use std::{fmt::Write as _, io, iter, mem, ops::RangeInclusive};
+
+use crate::{
+    escape::{
+        self,
+        csi::{AttributeChangeExtent, Sgr},
+        esc::CharacterSet,
+    },
+    style::{Blink, ColorSpec, CursorStyle, Font, Intensity, RgbaColor, Underline, VerticalAlign},
+    OneBased, WindowSize,
+};
+
+/// The limit on the length of the parameters of a CSI sequence and the contents of an OSC
+/// sequence. Anything beyond the limit is dropped.
+const MAX_SEQUENCE_LEN: usize = 1024;
+
+/// The graphic rendition of a cell, as set by `Sgr`s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub foreground: ColorSpec,
+    pub background: ColorSpec,
+    pub underline_color: ColorSpec,
+    pub intensity: Intensity,
+    pub underline: Underline,
+    pub blink: Blink,
+    pub italic: bool,
+    pub reverse: bool,
+    pub invisible: bool,
+    pub strike_through: bool,
+    pub overline: bool,
+    pub font: Font,
+    pub vertical_align: VerticalAlign,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            foreground: ColorSpec::Reset,
+            background: ColorSpec::Reset,
+            underline_color: ColorSpec::Reset,
+            intensity: Intensity::default(),
+            underline: Underline::default(),
+            blink: Blink::default(),
+            italic: false,
+            reverse: false,
+            invisible: false,
+            strike_through: false,
+            overline: false,
+            font: Font::default(),
+            vertical_align: VerticalAlign::default(),
+        }
+    }
+}
+
+impl Style {
+    /// The SGRs which produce this style when applied after `Sgr::Reset`.
+    pub fn sgrs(&self) -> Vec<Sgr> {
+        let default = Self::default();
+        let mut sgrs = Vec::new();
+        if self.intensity != default.intensity {
+            sgrs.push(Sgr::Intensity(self.intensity));
+        }
+        if self.underline != default.underline {
+            sgrs.push(Sgr::Underline(self.underline));
+        }
+        if self.blink != default.blink {
+            sgrs.push(Sgr::Blink(self.blink));
+        }
+        if self.italic {
+            sgrs.push(Sgr::Italic(true));
+        }
+        if self.reverse {
+            sgrs.push(Sgr::Reverse(true));
+        }
+        if self.invisible {
+            sgrs.push(Sgr::Invisible(true));
+        }
+        if self.strike_through {
+            sgrs.push(Sgr::StrikeThrough(true));
+        }
+        if self.overline {
+            sgrs.push(Sgr::Overline(true));
+        }
+        if self.font != default.font {
+            sgrs.push(Sgr::Font(self.font));
+        }
+        if self.vertical_align != default.vertical_align {
+            sgrs.push(Sgr::VerticalAlign(self.vertical_align));
+        }
+        if self.foreground != default.foreground {
+            sgrs.push(Sgr::Foreground(self.foreground));
+        }
+        if self.background != default.background {
+            sgrs.push(Sgr::Background(self.background));
+        }
+        if self.underline_color != default.underline_color {
+            sgrs.push(Sgr::UnderlineColor(self.underline_color));
+        }
+        sgrs
+    }
+}
+
+/// A single character position on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            ch: ' ',
+            style: Style::default(),
+        }
+    }
+}
+
+/// The state saved by DECSC (`Esc::SaveCursor`) and restored by DECRC.
+#[derive(Debug, Clone, Copy)]
+struct SavedCursor {
+    line: usize,
+    col: usize,
+    wrap_pending: bool,
+    style: Style,
+    origin_mode: bool,
+    charsets: [CharacterSet; 4],
+    shift_out: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+    /// OSC, DCS, APC, PM and SOS sequences which are terminated by ST. `escape` is set after an
+    /// ESC byte, which might be the start of the ST.
+    String {
+        is_osc: bool,
+        escape: bool,
+    },
+}
+
+/// A headless emulation of a terminal screen.
+///
+/// The screen consumes the bytes that an application writes to the terminal and keeps a grid of
+/// cells with their styles and a cursor, like a terminal emulator does. Tests can then assert on
+/// the screen contents with [Screen::text] or [Screen::styled_text] instead of on the escape
+/// sequences which produced them. For example a test against a `VirtualTerminal` can write the
+/// terminal's output into a screen.
+///
+/// The screen understands the sequences in `escape::csi` which affect the display: cursor
+/// movement, SGR, erasing, inserting and deleting characters and lines, scrolling, margins, tab
+/// stops, the rectangular area operations and the DEC private modes for the alternate screen,
+/// origin mode, auto-wrap, cursor visibility and left and right margins. It also understands the
+/// `escape::esc` sequences for saving and restoring the cursor, indexing, the character sets and
+/// resetting the terminal, and OSC window titles. Queries and other sequences are ignored.
+///
+/// Every character takes a single cell: wide characters and combining characters are not
+/// supported. There is no scrollback and the screen is not reflowed when it is resized.
+///
+/// ```
+/// # use std::io::Write as _;
+/// # use termina::{escape::csi::{self, Csi}, screen::Screen, OneBased, WindowSize};
+/// let size = WindowSize { cols: 10, rows: 2, pixel_width: None, pixel_height: None };
+/// let mut screen = Screen::new(size);
+/// let position = csi::Cursor::Position {
+///     line: OneBased::new(2).unwrap(),
+///     col: OneBased::new(3).unwrap(),
+/// };
+/// write!(screen, "hello{}world", Csi::Cursor(position))?;
+/// assert_eq!(screen.text(), "hello\n  world");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Screen {
+    rows: usize,
+    cols: usize,
+    lines: Vec<Vec<Cell>>,
+    /// The lines of the primary screen while the alternate screen is active.
+    primary: Option<Vec<Vec<Cell>>>,
+    line: usize,
+    col: usize,
+    /// Set when a character was printed in the last column with auto-wrap enabled. The cursor
+    /// stays in the last column and the next printed character wraps onto the next line.
+    wrap_pending: bool,
+    style: Style,
+    /// The scroll region. These are inclusive and zero-based.
+    top: usize,
+    bottom: usize,
+    left: usize,
+    right: usize,
+    origin_mode: bool,
+    auto_wrap: bool,
+    insert_mode: bool,
+    newline_mode: bool,
+    left_right_margin_mode: bool,
+    cursor_visible: bool,
+    cursor_style: CursorStyle,
+    tab_stops: Vec<bool>,
+    charsets: [CharacterSet; 4],
+    /// Whether G1 is active (SO) rather than G0 (SI).
+    shift_out: bool,
+    saved_cursor: Option<SavedCursor>,
+    /// The last printed character, for `Edit::Repeat`.
+    last_char: Option<char>,
+    attribute_change_extent: AttributeChangeExtent,
+    title: String,
+    state: State,
+    params: Vec<u8>,
+    intermediates: Vec<u8>,
+    osc: Vec<u8>,
+    utf8: Vec<u8>,
+}
+
+impl Screen {
+    pub fn new(size: WindowSize) -> Self {
+        let rows = size.rows.max(1) as usize;
+        let cols = size.cols.max(1) as usize;
+        Self {
+            rows,
+            cols,
+            lines: vec![vec![Cell::default(); cols]; rows],
+            primary: None,
+            line: 0,
+            col: 0,
+            wrap_pending: false,
+            style: Style::default(),
+            top: 0,
+            bottom: rows - 1,
+            left: 0,
+            right: cols - 1,
+            origin_mode: false,
+            auto_wrap: true,
+            insert_mode: false,
+            newline_mode: false,
+            left_right_margin_mode: false,
+            cursor_visible: true,
+            cursor_style: CursorStyle::Default,
+            tab_stops: (0..cols).map(is_default_tab_stop).collect(),
+            charsets: [CharacterSet::Ascii; 4],
+            shift_out: false,
+            saved_cursor: None,
+            last_char: None,
+            attribute_change_extent: AttributeChangeExtent::Default,
+            title: String::new(),
+            state: State::Ground,
+            params: Vec::new(),
+            intermediates: Vec::new(),
+            osc: Vec::new(),
+            utf8: Vec::new(),
+        }
+    }
+
+    pub fn size(&self) -> WindowSize {
+        WindowSize {
+            cols: self.cols as u16,
+            rows: self.rows as u16,
+            pixel_width: None,
+            pixel_height: None,
+        }
+    }
+
+    /// Changes the size of the screen.
+    ///
+    /// Lines and columns are added or removed at the bottom and right edges. The scroll region
+    /// is reset to the whole screen and the cursor is moved onto the screen if necessary.
+    pub fn resize(&mut self, size: WindowSize) {
+        let rows = size.rows.max(1) as usize;
+        let cols = size.cols.max(1) as usize;
+        for lines in iter::once(&mut self.lines).chain(self.primary.as_mut()) {
+            lines.resize(rows, vec![Cell::default(); cols]);
+            for line in lines.iter_mut() {
+                line.resize(cols, Cell::default());
+            }
+        }
+        let old_cols = self.tab_stops.len();
+        self.tab_stops.truncate(cols);
+        self.tab_stops
+            .extend((old_cols..cols).map(is_default_tab_stop));
+        self.rows = rows;
+        self.cols = cols;
+        self.reset_margins();
+        self.move_to(self.line, self.col);
+    }
+
+    /// Processes bytes written by an application. This is the same as writing the bytes with
+    /// the `io::Write` implementation.
+    ///
+    /// Sequences may be split across calls.
+    pub fn process(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.advance(byte);
+        }
+    }
+
+    /// Returns the cell at the given position or `None` if the position is off the screen.
+    pub fn cell(&self, line: OneBased, col: OneBased) -> Option<&Cell> {
+        self.lines
+            .get(line.get_zero_based() as usize)?
+            .get(col.get_zero_based() as usize)
+    }
+
+    /// The position of the cursor as `(line, col)`.
+    pub fn cursor_position(&self) -> (OneBased, OneBased) {
+        (
+            OneBased::from_zero_based(self.line as u16),
+            OneBased::from_zero_based(self.col as u16),
+        )
+    }
+
+    /// Whether the cursor is shown, as set by `DecPrivateModeCode::ShowCursor`.
+    pub fn is_cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    pub fn cursor_style(&self) -> CursorStyle {
+        self.cursor_style
+    }
+
+    /// Whether the alternate screen is active.
+    pub fn is_alternate_screen(&self) -> bool {
+        self.primary.is_some()
+    }
+
+    /// The window title, as set by `Osc::SetWindowTitle`.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// The text on the screen without styles.
+    ///
+    /// Lines are separated by `\n` and trailing whitespace is removed from each line. Every line
+    /// of the screen is included, even if it is empty.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            let line: String = line.iter().map(|cell| cell.ch).collect();
+            text.push_str(line.trim_end());
+        }
+        text
+    }
+
+    /// The text on the screen with styles.
+    ///
+    /// This is the same as [Self::text] except that an SGR sequence is inserted wherever the
+    /// style changes within a line. Each SGR sequence starts with a reset and then sets the
+    /// whole style (see [Style::sgrs]) so the output doesn't depend on how the application
+    /// reached the style. Lines which end with a style other than the default end with a reset.
+    /// Trailing cells are only removed if they are blank and unstyled.
+    pub fn styled_text(&self) -> String {
+        let mut text = String::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            let len = line
+                .iter()
+                .rposition(|cell| *cell != Cell::default())
+                .map_or(0, |i| i + 1);
+            let mut style = Style::default();
+            for cell in &line[..len] {
+                if cell.style != style {
+                    style = cell.style;
+                    text.push_str(escape::CSI);
+                    text.push('0');
+                    for sgr in style.sgrs() {
+                        let _ = write!(text, ";{sgr}");
+                    }
+                    text.push('m');
+                }
+                text.push(cell.ch);
+            }
+            if style != Style::default() {
+                text.push_str(escape::CSI);
+                text.push('m');
+            }
+        }
+        text
+    }
+
+    fn advance(&mut self, byte: u8) {
+        match self.state {
+            State::Ground => self.ground(byte),
+            State::Escape => self.escape(byte),
+            State::Csi => self.csi(byte),
+            State::String { is_osc, escape } => self.string(is_osc, escape, byte),
+        }
+    }
+
+    fn ground(&mut self, byte: u8) {
+        if !self.utf8.is_empty() || byte >= 0x80 {
+            self.utf8(byte);
+        } else if (0x20..0x7f).contains(&byte) {
+            self.print(byte as char);
+        } else {
+            self.execute(byte);
+        }
+    }
+
+    fn utf8(&mut self, byte: u8) {
+        let is_continuation = byte & 0xc0 == 0x80;
+        if !self.utf8.is_empty() && !is_continuation {
+            // The sequence was cut short.
+            self.utf8.clear();
+            self.print(char::REPLACEMENT_CHARACTER);
+            self.ground(byte);
+            return;
+        }
+        self.utf8.push(byte);
+        let len = match self.utf8[0] {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        if self.utf8.len() < len {
+            return;
+        }
+        let ch = std::str::from_utf8(&self.utf8)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+        self.utf8.clear();
+        // C1 control characters are ignored.
+        if !('\u{80}'..='\u{9f}').contains(&ch) {
+            self.print(ch);
+        }
+    }
+
+    /// Executes a C0 control character.
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            // BS
+            0x08 => {
+                let min = if self.col >= self.left { self.left } else { 0 };
+                self.move_to(self.line, self.col.saturating_sub(1).max(min));
+            }
+            // HT
+            0x09 => self.forward_tab(1),
+            // LF, VT, FF
+            0x0a..=0x0c => {
+                if self.newline_mode {
+                    self.carriage_return();
+                }
+                self.index();
+            }
+            // CR
+            0x0d => self.carriage_return(),
+            // SO, SI
+            0x0e => self.shift_out = true,
+            0x0f => self.shift_out = false,
+            // CAN, SUB
+            0x18 | 0x1a => self.state = State::Ground,
+            // ESC
+            0x1b => {
+                self.intermediates.clear();
+                self.state = State::Escape;
+            }
+            _ => (),
+        }
+    }
+
+    fn escape(&mut self, byte: u8) {
+        match byte {
+            0x20..=0x2f => self.intermediates.push(byte),
+            0x30..=0x7e => {
+                self.state = State::Ground;
+                self.escape_dispatch(byte);
+            }
+            0x00..=0x1f => self.execute(byte),
+            _ => (),
+        }
+    }
+
+    fn escape_dispatch(&mut self, byte: u8) {
+        let intermediates = mem::take(&mut self.intermediates);
+        match (intermediates.as_slice(), byte) {
+            ([], b'[') => {
+                self.params.clear();
+                self.state = State::Csi;
+            }
+            ([], b']') => {
+                self.osc.clear();
+                self.state = State::String {
+                    is_osc: true,
+                    escape: false,
+                };
+            }
+            // DCS, SOS, PM, APC
+            ([], b'P' | b'X' | b'^' | b'_') => {
+                self.state = State::String {
+                    is_osc: false,
+                    escape: false,
+                };
+            }
+            ([], b'7') => self.save_cursor(),
+            ([], b'8') => self.restore_cursor(),
+            ([], b'D') => self.index(),
+            ([], b'E') => {
+                self.carriage_return();
+                self.index();
+            }
+            ([], b'M') => self.reverse_index(),
+            ([], b'H') => self.tab_stops[self.col] = true,
+            ([], b'c') => *self = Self::new(self.size()),
+            // DECALN
+            ([b'#'], b'8') => {
+                let cell = Cell {
+                    ch: 'E',
+                    style: Style::default(),
+                };
+                for line in self.lines.iter_mut() {
+                    line.fill(cell);
+                }
+                self.reset_margins();
+                self.move_to(0, 0);
+            }
+            // SCS
+            ([slot @ b'('..=b'+'], designator) => {
+                self.charsets[(slot - b'(') as usize] = match designator {
+                    b'A' => CharacterSet::Uk,
+                    b'0' => CharacterSet::DecLineDrawing,
+                    _ => CharacterSet::Ascii,
+                };
+            }
+            _ => (),
+        }
+    }
+
+    fn csi(&mut self, byte: u8) {
+        match byte {
+            0x30..=0x3f if self.params.len() < MAX_SEQUENCE_LEN => self.params.push(byte),
+            0x30..=0x3f => (),
+            0x20..=0x2f => self.intermediates.push(byte),
+            0x40..=0x7e => {
+                self.state = State::Ground;
+                self.csi_dispatch(byte);
+            }
+            0x00..=0x1f => self.execute(byte),
+            _ => (),
+        }
+    }
+
+    fn csi_dispatch(&mut self, byte: u8) {
+        let intermediates = mem::take(&mut self.intermediates);
+        let (marker, params) = parse_params(&self.params);
+        let params = params.as_slice();
+        let count = |i: usize| param(params, i, 1) as usize;
+
+        match (marker, intermediates.as_slice(), byte) {
+            (None, [], b'@') => self.insert_characters(count(0)),
+            (None, [], b'A' | b'k') => self.cursor_up(count(0)),
+            (None, [], b'B' | b'e') => self.cursor_down(count(0)),
+            (None, [], b'C' | b'a') => self.cursor_right(count(0)),
+            (None, [], b'D' | b'j') => self.cursor_left(count(0)),
+            (None, [], b'E') => {
+                self.cursor_down(count(0));
+                self.carriage_return();
+            }
+            (None, [], b'F') => {
+                self.cursor_up(count(0));
+                self.carriage_return();
+            }
+            (None, [], b'G' | b'`') => self.set_col(param(params, 0, 1)),
+            (None, [], b'H' | b'f') => {
+                self.set_line(param(params, 0, 1));
+                self.set_col(param(params, 1, 1));
+            }
+            (None, [], b'I') => self.forward_tab(count(0)),
+            (None, [], b'J') => self.erase_in_display(param(params, 0, 0)),
+            (None, [], b'K') => self.erase_in_line(param(params, 0, 0)),
+            (None, [], b'L') => self.insert_lines(count(0)),
+            (None, [], b'M') => self.delete_lines(count(0)),
+            (None, [], b'P') => self.delete_characters(count(0)),
+            (None, [], b'S') => self.scroll_up(self.top, count(0)),
+            (None, [], b'T') => self.scroll_down(self.top, count(0)),
+            (None, [], b'X') => self.erase_characters(count(0)),
+            (None, [], b'Z') => self.backward_tab(count(0)),
+            (None, [], b'b') => {
+                if let Some(ch) = self.last_char {
+                    for _ in 0..count(0).min(self.rows * self.cols) {
+                        self.print(ch);
+                    }
+                }
+            }
+            (None, [], b'd') => self.set_line(param(params, 0, 1)),
+            (None, [], b'g') => match param(params, 0, 0) {
+                0 => self.tab_stops[self.col] = false,
+                3 | 5 => self.tab_stops.fill(false),
+                _ => (),
+            },
+            (None, [], b'h' | b'l') => {
+                for mode in params.iter().filter_map(|param| param.first()) {
+                    match mode {
+                        4 => self.insert_mode = byte == b'h',
+                        20 => self.newline_mode = byte == b'h',
+                        _ => (),
+                    }
+                }
+            }
+            (None, [], b'm') => apply_sgr(&mut self.style, params),
+            (None, [], b'r') => {
+                let top = param(params, 0, 1) as usize - 1;
+                let bottom = (param(params, 1, self.rows as u32) as usize).min(self.rows) - 1;
+                if top < bottom {
+                    self.top = top;
+                    self.bottom = bottom;
+                    self.home();
+                }
+            }
+            (None, [], b's') if self.left_right_margin_mode => {
+                let left = param(params, 0, 1) as usize - 1;
+                let right = (param(params, 1, self.cols as u32) as usize).min(self.cols) - 1;
+                if left < right {
+                    self.left = left;
+                    self.right = right;
+                    self.home();
+                }
+            }
+            (None, [], b's') => self.save_cursor(),
+            (None, [], b'u') => self.restore_cursor(),
+            (Some(b'?'), [], b'h' | b'l') => {
+                for mode in params.iter().filter_map(|param| param.first()) {
+                    self.set_dec_private_mode(*mode, byte == b'h');
+                }
+            }
+            // DECSCUSR
+            (None, [b' '], b'q') => {
+                self.cursor_style = match param(params, 0, 0) {
+                    1 => CursorStyle::BlinkingBlock,
+                    2 => CursorStyle::SteadyBlock,
+                    3 => CursorStyle::BlinkingUnderline,
+                    4 => CursorStyle::SteadyUnderline,
+                    5 => CursorStyle::BlinkingBar,
+                    6 => CursorStyle::SteadyBar,
+                    _ => CursorStyle::Default,
+                }
+            }
+            // DECSTR
+            (None, [b'!'], b'p') => self.soft_reset(),
+            // DECFRA
+            (None, [b'$'], b'x') => {
+                let ch = char::from_u32(param(params, 0, 0)).filter(|ch| !ch.is_control());
+                if let (Some(ch), Some(area)) = (ch, self.area(params, 1)) {
+                    let cell = Cell {
+                        ch,
+                        style: self.style,
+                    };
+                    self.for_each_cell(area, false, |c| *c = cell);
+                }
+            }
+            // DECERA, DECSERA. Characters are never protected so these are the same.
+            (None, [b'$'], b'z' | b'{') => {
+                if let Some(area) = self.area(params, 0) {
+                    self.for_each_cell(area, false, |c| *c = Cell::default());
+                }
+            }
+            // DECCRA
+            (None, [b'$'], b'v') => self.copy_area(params),
+            // DECCARA
+            (None, [b'$'], b'r') => {
+                if let Some(area) = self.area(params, 0) {
+                    let attributes = &params[4.min(params.len())..];
+                    let stream = self.attribute_change_extent != AttributeChangeExtent::Rectangle;
+                    self.for_each_cell(area, stream, |cell| apply_sgr(&mut cell.style, attributes));
+                }
+            }
+            // DECRARA
+            (None, [b'$'], b't') => {
+                if let Some(area) = self.area(params, 0) {
+                    let attributes = &params[4.min(params.len())..];
+                    let stream = self.attribute_change_extent != AttributeChangeExtent::Rectangle;
+                    self.for_each_cell(area, stream, |cell| {
+                        reverse_attributes(&mut cell.style, attributes)
+                    });
+                }
+            }
+            // DECSACE
+            (None, [b'*'], b'x') => {
+                self.attribute_change_extent = match param(params, 0, 0) {
+                    1 => AttributeChangeExtent::Stream,
+                    2 => AttributeChangeExtent::Rectangle,
+                    _ => AttributeChangeExtent::Default,
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn string(&mut self, is_osc: bool, escape: bool, byte: u8) {
+        if escape {
+            self.state = State::Ground;
+            if is_osc {
+                self.osc_dispatch();
+            }
+            // Anything other than ST aborts the string and starts a new escape sequence.
+            if byte != b'\\' {
+                self.execute(0x1b);
+                self.advance(byte);
+            }
+            return;
+        }
+        match byte {
+            // BEL, which terminates OSC sequences in xterm.
+            0x07 => {
+                self.state = State::Ground;
+                if is_osc {
+                    self.osc_dispatch();
+                }
+            }
+            0x18 | 0x1a => self.state = State::Ground,
+            0x1b => {
+                self.state = State::String {
+                    is_osc,
+                    escape: true,
+                }
+            }
+            _ => {
+                if is_osc && self.osc.len() < MAX_SEQUENCE_LEN {
+                    self.osc.push(byte);
+                }
+            }
+        }
+    }
+
+    fn osc_dispatch(&mut self) {
+        let osc = String::from_utf8_lossy(&self.osc);
+        if let Some((code, title)) = osc.split_once(';') {
+            if code == "0" || code == "2" {
+                self.title = title.to_string();
+            }
+        }
+    }
+
+    fn print(&mut self, ch: char) {
+        let charset = self.charsets[usize::from(self.shift_out)];
+        let ch = translate_charset(charset, ch);
+        if self.wrap_pending && self.auto_wrap {
+            self.carriage_return();
+            self.index();
+        }
+        let right = if self.col <= self.right {
+            self.right
+        } else {
+            self.cols - 1
+        };
+        if self.insert_mode {
+            self.insert_characters(1);
+        }
+        self.lines[self.line][self.col] = Cell {
+            ch,
+            style: self.style,
+        };
+        self.last_char = Some(ch);
+        if self.col < right {
+            self.col += 1;
+            self.wrap_pending = false;
+        } else {
+            self.wrap_pending = self.auto_wrap;
+        }
+    }
+
+    /// A blank cell for erasing. Erased cells keep the current background color like in xterm.
+    fn blank(&self) -> Cell {
+        Cell {
+            ch: ' ',
+            style: Style {
+                background: self.style.background,
+                ..Style::default()
+            },
+        }
+    }
+
+    fn move_to(&mut self, line: usize, col: usize) {
+        self.line = line.min(self.rows - 1);
+        self.col = col.min(self.cols - 1);
+        self.wrap_pending = false;
+    }
+
+    fn home(&mut self) {
+        if self.origin_mode {
+            self.move_to(self.top, self.left);
+        } else {
+            self.move_to(0, 0);
+        }
+    }
+
+    /// Moves to a one-based line, relative to the scroll region in origin mode.
+    fn set_line(&mut self, line: u32) {
+        let line = line as usize - 1;
+        if self.origin_mode {
+            self.move_to((self.top + line).min(self.bottom), self.col);
+        } else {
+            self.move_to(line, self.col);
+        }
+    }
+
+    /// Moves to a one-based column, relative to the scroll region in origin mode.
+    fn set_col(&mut self, col: u32) {
+        let col = col as usize - 1;
+        if self.origin_mode {
+            self.move_to(self.line, (self.left + col).min(self.right));
+        } else {
+            self.move_to(self.line, col);
+        }
+    }
+
+    fn cursor_up(&mut self, n: usize) {
+        let min = if self.line >= self.top { self.top } else { 0 };
+        self.move_to(self.line.saturating_sub(n).max(min), self.col);
+    }
+
+    fn cursor_down(&mut self, n: usize) {
+        let max = if self.line <= self.bottom {
+            self.bottom
+        } else {
+            self.rows - 1
+        };
+        self.move_to((self.line + n).min(max), self.col);
+    }
+
+    fn cursor_right(&mut self, n: usize) {
+        let max = if self.col <= self.right {
+            self.right
+        } else {
+            self.cols - 1
+        };
+        self.move_to(self.line, (self.col + n).min(max));
+    }
+
+    fn cursor_left(&mut self, n: usize) {
+        let min = if self.col >= self.left { self.left } else { 0 };
+        self.move_to(self.line, self.col.saturating_sub(n).max(min));
+    }
+
+    fn carriage_return(&mut self) {
+        let col = if self.col >= self.left { self.left } else { 0 };
+        self.move_to(self.line, col);
+    }
+
+    fn forward_tab(&mut self, n: usize) {
+        let max = if self.col <= self.right {
+            self.right
+        } else {
+            self.cols - 1
+        };
+        let mut col = self.col;
+        for _ in 0..n {
+            col = (col + 1..max)
+                .find(|&col| self.tab_stops[col])
+                .unwrap_or(max);
+        }
+        self.move_to(self.line, col);
+    }
+
+    fn backward_tab(&mut self, n: usize) {
+        let min = if self.col >= self.left { self.left } else { 0 };
+        let mut col = self.col;
+        for _ in 0..n {
+            col = (min + 1..col)
+                .rev()
+                .find(|&col| self.tab_stops[col])
+                .unwrap_or(min);
+        }
+        self.move_to(self.line, col);
+    }
+
+    fn index(&mut self) {
+        if self.line == self.bottom {
+            self.scroll_up(self.top, 1);
+        } else {
+            self.move_to(self.line + 1, self.col);
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        if self.line == self.top {
+            self.scroll_down(self.top, 1);
+        } else {
+            self.move_to(self.line.saturating_sub(1), self.col);
+        }
+    }
+
+    /// Moves the lines from `top` to the bottom margin up by `n`, within the left and right
+    /// margins.
+    fn scroll_up(&mut self, top: usize, n: usize) {
+        let blank = self.blank();
+        let columns = self.left..=self.right;
+        for line in top..=self.bottom {
+            let source = line + n;
+            if source <= self.bottom {
+                let (destination, source) = self.lines.split_at_mut(source);
+                destination[line][columns.clone()].copy_from_slice(&source[0][columns.clone()]);
+            } else {
+                self.lines[line][columns.clone()].fill(blank);
+            }
+        }
+    }
+
+    /// Moves the lines from `top` to the bottom margin down by `n`, within the left and right
+    /// margins.
+    fn scroll_down(&mut self, top: usize, n: usize) {
+        let blank = self.blank();
+        let columns = self.left..=self.right;
+        for line in (top..=self.bottom).rev() {
+            if line >= top + n {
+                let (source, destination) = self.lines.split_at_mut(line);
+                destination[0][columns.clone()].copy_from_slice(&source[line - n][columns.clone()]);
+            } else {
+                self.lines[line][columns.clone()].fill(blank);
+            }
+        }
+    }
+
+    fn is_in_scroll_region(&self) -> bool {
+        (self.top..=self.bottom).contains(&self.line)
+            && (self.left..=self.right).contains(&self.col)
+    }
+
+    fn insert_lines(&mut self, n: usize) {
+        if self.is_in_scroll_region() {
+            self.scroll_down(self.line, n);
+            self.move_to(self.line, self.left);
+        }
+    }
+
+    fn delete_lines(&mut self, n: usize) {
+        if self.is_in_scroll_region() {
+            self.scroll_up(self.line, n);
+            self.move_to(self.line, self.left);
+        }
+    }
+
+    /// The columns affected by inserting and deleting characters at the cursor.
+    fn edit_columns(&self) -> RangeInclusive<usize> {
+        let right = if self.col <= self.right {
+            self.right
+        } else {
+            self.cols - 1
+        };
+        self.col..=right
+    }
+
+    fn insert_characters(&mut self, n: usize) {
+        let blank = self.blank();
+        let (col, right) = self.edit_columns().into_inner();
+        let n = n.min(right + 1 - col);
+        let line = &mut self.lines[self.line];
+        line.copy_within(col..right + 1 - n, col + n);
+        line[col..col + n].fill(blank);
+        self.wrap_pending = false;
+    }
+
+    fn delete_characters(&mut self, n: usize) {
+        let blank = self.blank();
+        let (col, right) = self.edit_columns().into_inner();
+        let n = n.min(right + 1 - col);
+        let line = &mut self.lines[self.line];
+        line.copy_within(col + n..=right, col);
+        line[right + 1 - n..=right].fill(blank);
+        self.wrap_pending = false;
+    }
+
+    fn erase_characters(&mut self, n: usize) {
+        let blank = self.blank();
+        let end = (self.col + n).min(self.cols);
+        self.lines[self.line][self.col..end].fill(blank);
+        self.wrap_pending = false;
+    }
+
+    fn erase_in_line(&mut self, mode: u32) {
+        let blank = self.blank();
+        let line = &mut self.lines[self.line];
+        match mode {
+            0 => line[self.col..].fill(blank),
+            1 => line[..=self.col].fill(blank),
+            2 => line.fill(blank),
+            _ => (),
+        }
+        self.wrap_pending = false;
+    }
+
+    fn erase_in_display(&mut self, mode: u32) {
+        let blank = self.blank();
+        let lines = match mode {
+            0 => {
+                self.erase_in_line(0);
+                self.line + 1..self.rows
+            }
+            1 => {
+                self.erase_in_line(1);
+                0..self.line
+            }
+            2 => 0..self.rows,
+            // There is no scrollback to erase.
+            _ => return,
+        };
+        for line in &mut self.lines[lines] {
+            line.fill(blank);
+        }
+    }
+
+    /// Reads a rectangle from four parameters starting at `start`. Returns the zero-based
+    /// `(top, left, bottom, right)` or `None` if the rectangle is empty.
+    fn area(&self, params: &[Vec<u32>], start: usize) -> Option<(usize, usize, usize, usize)> {
+        let (line_offset, col_offset, max_line, max_col) = if self.origin_mode {
+            (self.top, self.left, self.bottom, self.right)
+        } else {
+            (0, 0, self.rows - 1, self.cols - 1)
+        };
+        let top = (line_offset + param(params, start, 1) as usize - 1).min(max_line);
+        let left = (col_offset + param(params, start + 1, 1) as usize - 1).min(max_col);
+        let bottom =
+            (line_offset + param(params, start + 2, u32::MAX).min(u16::MAX as u32) as usize - 1)
+                .min(max_line);
+        let right = (col_offset + param(params, start + 3, u32::MAX).min(u16::MAX as u32) as usize
+            - 1)
+        .min(max_col);
+        (top <= bottom && left <= right).then_some((top, left, bottom, right))
+    }
+
+    /// Calls `f` on each cell of the area. If `stream` is set then the area is the stream of
+    /// cells from the top-left to the bottom-right, wrapping at the ends of lines, rather than
+    /// the rectangle.
+    fn for_each_cell(
+        &mut self,
+        (top, left, bottom, right): (usize, usize, usize, usize),
+        stream: bool,
+        mut f: impl FnMut(&mut Cell),
+    ) {
+        for line in top..=bottom {
+            let columns = if !stream || top == bottom {
+                left..=right
+            } else if line == top {
+                left..=self.cols - 1
+            } else if line == bottom {
+                0..=right
+            } else {
+                0..=self.cols - 1
+            };
+            self.lines[line][columns].iter_mut().for_each(&mut f);
+        }
+    }
+
+    fn copy_area(&mut self, params: &[Vec<u32>]) {
+        let Some((top, left, bottom, right)) = self.area(params, 0) else {
+            return;
+        };
+        let (line_offset, col_offset) = if self.origin_mode {
+            (self.top, self.left)
+        } else {
+            (0, 0)
+        };
+        let destination_top = line_offset + param(params, 5, 1) as usize - 1;
+        let destination_left = col_offset + param(params, 6, 1) as usize - 1;
+        // Copy the source first in case the areas overlap.
+        let source: Vec<Vec<Cell>> = self.lines[top..=bottom]
+            .iter()
+            .map(|line| line[left..=right].to_vec())
+            .collect();
+        for (i, source_line) in source.iter().enumerate() {
+            let Some(line) = self.lines.get_mut(destination_top + i) else {
+                break;
+            };
+            for (j, cell) in source_line.iter().enumerate() {
+                if let Some(destination) = line.get_mut(destination_left + j) {
+                    *destination = *cell;
+                }
+            }
+        }
+    }
+
+    fn set_dec_private_mode(&mut self, mode: u32, enable: bool) {
+        match mode {
+            6 => {
+                self.origin_mode = enable;
+                self.home();
+            }
+            7 => self.auto_wrap = enable,
+            25 => self.cursor_visible = enable,
+            47 | 1047 => {
+                if enable {
+                    self.enter_alternate_screen();
+                } else {
+                    self.exit_alternate_screen();
+                }
+            }
+            69 => {
+                self.left_right_margin_mode = enable;
+                if !enable {
+                    self.left = 0;
+                    self.right = self.cols - 1;
+                }
+            }
+            1048 => {
+                if enable {
+                    self.save_cursor();
+                } else {
+                    self.restore_cursor();
+                }
+            }
+            1049 => {
+                if enable {
+                    self.save_cursor();
+                    self.enter_alternate_screen();
+                } else {
+                    self.exit_alternate_screen();
+                    self.restore_cursor();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Switches to a blank alternate screen.
+    fn enter_alternate_screen(&mut self) {
+        if self.primary.is_none() {
+            let alternate = vec![vec![Cell::default(); self.cols]; self.rows];
+            self.primary = Some(mem::replace(&mut self.lines, alternate));
+        }
+    }
+
+    fn exit_alternate_screen(&mut self) {
+        if let Some(primary) = self.primary.take() {
+            self.lines = primary;
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = Some(SavedCursor {
+            line: self.line,
+            col: self.col,
+            wrap_pending: self.wrap_pending,
+            style: self.style,
+            origin_mode: self.origin_mode,
+            charsets: self.charsets,
+            shift_out: self.shift_out,
+        });
+    }
+
+    fn restore_cursor(&mut self) {
+        let saved = self.saved_cursor.unwrap_or(SavedCursor {
+            line: 0,
+            col: 0,
+            wrap_pending: false,
+            style: Style::default(),
+            origin_mode: false,
+            charsets: [CharacterSet::Ascii; 4],
+            shift_out: false,
+        });
+        self.move_to(saved.line, saved.col);
+        self.wrap_pending = saved.wrap_pending;
+        self.style = saved.style;
+        self.origin_mode = saved.origin_mode;
+        self.charsets = saved.charsets;
+        self.shift_out = saved.shift_out;
+    }
+
+    fn reset_margins(&mut self) {
+        self.top = 0;
+        self.bottom = self.rows - 1;
+        self.left = 0;
+        self.right = self.cols - 1;
+    }
+
+    fn soft_reset(&mut self) {
+        self.cursor_visible = true;
+        self.insert_mode = false;
+        self.origin_mode = false;
+        self.auto_wrap = true;
+        self.left_right_margin_mode = false;
+        self.reset_margins();
+        self.style = Style::default();
+        self.charsets = [CharacterSet::Ascii; 4];
+        self.shift_out = false;
+        self.saved_cursor = None;
+    }
+}
+
+impl io::Write for Screen {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.process(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn is_default_tab_stop(col: usize) -> bool {
+    col % 8 == 0
+}
+
+/// Splits CSI parameters into their `;` separated parameters and `:` separated sub-parameters.
+/// Empty (sub-)parameters are zero. Also returns the private marker, for example `?`.
+fn parse_params(bytes: &[u8]) -> (Option<u8>, Vec<Vec<u32>>) {
+    let (marker, bytes) = match bytes.first() {
+        Some(marker @ b'<'..=b'?') => (Some(*marker), &bytes[1..]),
+        _ => (None, bytes),
+    };
+    if bytes.is_empty() {
+        return (marker, Vec::new());
+    }
+    let params = bytes
+        .split(|&b| b == b';')
+        .map(|param| {
+            param
+                .split(|&b| b == b':')
+                .map(|digits| {
+                    digits.iter().fold(0u32, |n, &b| {
+                        n.saturating_mul(10)
+                            .saturating_add(b.wrapping_sub(b'0') as u32)
+                    })
+                })
+                .collect()
+        })
+        .collect();
+    (marker, params)
+}
+
+/// Returns the parameter at `i` or `default` if it is missing or zero.
+fn param(params: &[Vec<u32>], i: usize, default: u32) -> u32 {
+    match params.get(i).and_then(|param| param.first()) {
+        None | Some(0) => default,
+        Some(n) => *n,
+    }
+}
+
+fn apply_sgr(style: &mut Style, params: &[Vec<u32>]) {
+    if params.is_empty() {
+        *style = Style::default();
+        return;
+    }
+    let mut i = 0;
+    while i < params.len() {
+        let param = &params[i];
+        let code = param.first().copied().unwrap_or(0);
+        match code {
+            0 => *style = Style::default(),
+            1 => style.intensity = Intensity::Bold,
+            2 => style.intensity = Intensity::Dim,
+            3 => style.italic = true,
+            4 => {
+                style.underline = match param.get(1) {
+                    None | Some(1) => Underline::Single,
+                    Some(0) => Underline::None,
+                    Some(2) => Underline::Double,
+                    Some(3) => Underline::Curly,
+                    Some(4) => Underline::Dotted,
+                    Some(5) => Underline::Dashed,
+                    Some(_) => style.underline,
+                }
+            }
+            5 => style.blink = Blink::Slow,
+            6 => style.blink = Blink::Rapid,
+            7 => style.reverse = true,
+            8 => style.invisible = true,
+            9 => style.strike_through = true,
+            10 => style.font = Font::Default,
+            11..=19 => style.font = Font::Alternate((code - 10) as u8),
+            21 => style.underline = Underline::Double,
+            22 => style.intensity = Intensity::Normal,
+            23 => style.italic = false,
+            24 => style.underline = Underline::None,
+            25 => style.blink = Blink::None,
+            27 => style.reverse = false,
+            28 => style.invisible = false,
+            29 => style.strike_through = false,
+            30..=37 => style.foreground = ColorSpec::PaletteIndex((code - 30) as u8),
+            38 => {
+                if let Some(color) = parse_color(params, &mut i) {
+                    style.foreground = color;
+                }
+            }
+            39 => style.foreground = ColorSpec::Reset,
+            40..=47 => style.background = ColorSpec::PaletteIndex((code - 40) as u8),
+            48 => {
+                if let Some(color) = parse_color(params, &mut i) {
+                    style.background = color;
+                }
+            }
+            49 => style.background = ColorSpec::Reset,
+            53 => style.overline = true,
+            55 => style.overline = false,
+            58 => {
+                if let Some(color) = parse_color(params, &mut i) {
+                    style.underline_color = color;
+                }
+            }
+            59 => style.underline_color = ColorSpec::Reset,
+            73 => style.vertical_align = VerticalAlign::SuperScript,
+            74 => style.vertical_align = VerticalAlign::SubScript,
+            75 => style.vertical_align = VerticalAlign::BaseLine,
+            90..=97 => style.foreground = ColorSpec::PaletteIndex((code - 90 + 8) as u8),
+            100..=107 => style.background = ColorSpec::PaletteIndex((code - 100 + 8) as u8),
+            _ => (),
+        }
+        i += 1;
+    }
+}
+
+/// Parses the color of an extended color SGR (38, 48 or 58) at `params[*i]`.
+///
+/// The color is either in the sub-parameters, like `38:2::255:0:0`, or in the following
+/// parameters, like `38;2;255;0;0`. In the latter case `i` is advanced past the color.
+fn parse_color(params: &[Vec<u32>], i: &mut usize) -> Option<ColorSpec> {
+    fn rgba(red: u32, green: u32, blue: u32, alpha: u32) -> ColorSpec {
+        let byte = |n: u32| n.min(255) as u8;
+        ColorSpec::TrueColor(RgbaColor {
+            red: byte(red),
+            green: byte(green),
+            blue: byte(blue),
+            alpha: byte(alpha),
+        })
+    }
+
+    if let [_, sub_params @ ..] = params[*i].as_slice() {
+        if !sub_params.is_empty() {
+            return match *sub_params {
+                [5, index, ..] => Some(ColorSpec::PaletteIndex(index.min(255) as u8)),
+                // The color space ID is optional.
+                [2, _, red, green, blue, ..] | [2, red, green, blue] => {
+                    Some(rgba(red, green, blue, 255))
+                }
+                [6, _, red, green, blue, alpha, ..] => Some(rgba(red, green, blue, alpha)),
+                _ => None,
+            };
+        }
+    }
+    let value = |n: usize| params.get(*i + n).and_then(|param| param.first()).copied();
+    match value(1)? {
+        5 => {
+            let index = value(2)?;
+            *i += 2;
+            Some(ColorSpec::PaletteIndex(index.min(255) as u8))
+        }
+        2 => {
+            let (red, green, blue) = (value(2)?, value(3)?, value(4)?);
+            *i += 4;
+            Some(rgba(red, green, blue, 255))
+        }
+        _ => None,
+    }
+}
+
+/// Toggles attributes for DECRARA.
+fn reverse_attributes(style: &mut Style, params: &[Vec<u32>]) {
+    let codes = params
+        .iter()
+        .map(|param| param.first().copied().unwrap_or(0));
+    let codes: Vec<u32> = if params.is_empty() {
+        vec![0]
+    } else {
+        codes.collect()
+    };
+    for code in codes {
+        let all = code == 0;
+        if all || code == 1 {
+            style.intensity = match style.intensity {
+                Intensity::Bold => Intensity::Normal,
+                _ => Intensity::Bold,
+            };
+        }
+        if all || code == 4 {
+            style.underline = match style.underline {
+                Underline::None => Underline::Single,
+                _ => Underline::None,
+            };
+        }
+        if all || code == 5 {
+            style.blink = match style.blink {
+                Blink::None => Blink::Slow,
+                _ => Blink::None,
+            };
+        }
+        if all || code == 7 {
+            style.reverse = !style.reverse;
+        }
+    }
+}
+
+/// Maps a character through the active character set.
+fn translate_charset(charset: CharacterSet, ch: char) -> char {
+    match (charset, ch) {
+        (CharacterSet::Uk, '#') => '£',
+        (CharacterSet::DecLineDrawing, '_'..='~') => {
+            const LINE_DRAWING: [char; 32] = [
+                ' ', '◆', '▒', '␉', '␌', '␍', '␊', '°', '±', '␤', '␋', '┘', '┐', '┌', '└', '┼',
+                '⎺', '⎻', '─', '⎼', '⎽', '├', '┤', '┴', '┬', '│', '≤', '≥', 'π', '≠', '£', '·',
+            ];
+            LINE_DRAWING[ch as usize - '_' as usize]
+        }
+        _ => ch,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write as _;
+
+    use crate::escape::{
+        csi::{self, Csi, DecPrivateMode, DecPrivateModeCode},
+        esc::{CharacterSet, CharacterSetSlot, Esc},
+    };
+
+    use super::*;
+
+    fn screen(cols: u16, rows: u16) -> Screen {
+        Screen::new(WindowSize {
+            cols,
+            rows,
+            pixel_width: None,
+            pixel_height: None,
+        })
+    }
+
+    fn one_based(n: u16) -> OneBased {
+        OneBased::new(n).unwrap()
+    }
+
+    #[test]
+    fn cursor_movement_and_wrapping() {
+        let mut screen = screen(5, 2);
+        write!(
+            screen,
+            "abcdefg{}{}x\r\n\ty",
+            Csi::Cursor(csi::Cursor::Up(1)),
+            Csi::Cursor(csi::Cursor::Left(2)),
+        )
+        .unwrap();
+        assert_eq!(screen.text(), "xbcde\nfg  y");
+        assert_eq!(screen.cursor_position(), (one_based(2), one_based(5)));
+
+        // Printing in the last column defers the wrap until the next character so the screen
+        // scrolls.
+        write!(screen, "z").unwrap();
+        assert_eq!(screen.text(), "fg  y\nz");
+    }
+
+    #[test]
+    fn styles() {
+        let mut screen = screen(20, 1);
+        write!(
+            screen,
+            "\x1b[1;31mbold\x1b[22;38:2::1:2:3m rgb\x1b[m \x1b[4:3;58;5;1mcurly\x1b[m",
+        )
+        .unwrap();
+        assert_eq!(screen.text(), "bold rgb curly");
+        assert_eq!(
+            screen.styled_text(),
+            "\x1b[0;1;31mbold\x1b[0;38;2;1;2;3m rgb\x1b[0m \x1b[0;4:3;58:5:1mcurly\x1b[m"
+        );
+        assert_eq!(
+            screen.cell(one_based(1), one_based(1)).unwrap().style,
+            Style {
+                foreground: ColorSpec::RED,
+                intensity: Intensity::Bold,
+                ..Style::default()
+            }
+        );
+    }
+
+    #[test]
+    fn editing() {
+        let mut screen = screen(6, 4);
+        write!(screen, "111\r\n222\r\n333\r\n444").unwrap();
+        // Scroll the middle two lines, insert a line into them and repeat a character.
+        write!(
+            screen,
+            "{}{}{}{}{}x{}",
+            Csi::Cursor(csi::Cursor::SetTopAndBottomMargins {
+                top: one_based(2),
+                bottom: one_based(3),
+            }),
+            Csi::Edit(csi::Edit::ScrollUp(1)),
+            Csi::Cursor(csi::Cursor::LinePositionAbsolute(2)),
+            Csi::Edit(csi::Edit::InsertLine(1)),
+            Csi::Cursor(csi::Cursor::Position {
+                line: one_based(2),
+                col: one_based(2),
+            }),
+            Csi::Edit(csi::Edit::Repeat(2)),
+        )
+        .unwrap();
+        assert_eq!(screen.text(), "111\n xxx\n333\n444");
+
+        write!(
+            screen,
+            "{}{}",
+            Csi::Cursor(csi::Cursor::CharacterAbsolute(one_based(1))),
+            Csi::Edit(csi::Edit::DeleteCharacter(2)),
+        )
+        .unwrap();
+        assert_eq!(screen.text(), "111\nxx\n333\n444");
+    }
+
+    #[test]
+    fn alternate_screen_and_line_drawing() {
+        let mut screen = screen(4, 2);
+        write!(screen, "main").unwrap();
+        let mode = DecPrivateMode::Code(DecPrivateModeCode::ClearAndEnableAlternateScreen);
+        write!(
+            screen,
+            "{}{}{}lqk{}",
+            Csi::Mode(csi::Mode::SetDecPrivateMode(mode)),
+            Csi::Cursor(csi::Cursor::Position {
+                line: one_based(1),
+                col: one_based(1),
+            }),
+            Esc::DesignateCharacterSet {
+                slot: CharacterSetSlot::G0,
+                charset: CharacterSet::DecLineDrawing,
+            },
+            Esc::DesignateCharacterSet {
+                slot: CharacterSetSlot::G0,
+                charset: CharacterSet::Ascii,
+            },
+        )
+        .unwrap();
+        assert!(screen.is_alternate_screen());
+        assert_eq!(screen.text(), "┌─┐\n");
+
+        write!(
+            screen,
+            "{}",
+            Csi::Mode(csi::Mode::ResetDecPrivateMode(mode))
+        )
+        .unwrap();
+        assert!(!screen.is_alternate_screen());
+        assert_eq!(screen.text(), "main\n");
+        // The cursor was saved in the last column with a wrap pending.
+        write!(screen, "!").unwrap();
+        assert_eq!(screen.text(), "main\n!");
+    }
+
+    #[test]
+    fn scroll_margins_with_origin_mode() {
+        let mut screen = screen(5, 4);
+        write!(screen, "aaaaa\r\nbbbbb\r\nccccc\r\nddddd").unwrap();
+        // Margins on lines 2-3 and columns 2-4, with the cursor addressed relative to them.
+        write!(screen, "\x1b[?69h\x1b[2;3r\x1b[2;4s\x1b[?6h").unwrap();
+        assert_eq!(screen.cursor_position(), (one_based(2), one_based(2)));
+
+        // Text wraps at the right margin back to the left margin.
+        write!(screen, "\x1b[1;1HXYZW").unwrap();
+        assert_eq!(screen.text(), "aaaaa\nbXYZb\ncWccc\nddddd");
+
+        // Only the area within the margins scrolls.
+        write!(screen, "\x1b[S").unwrap();
+        assert_eq!(screen.text(), "aaaaa\nbWccb\nc   c\nddddd");
+
+        // Positions are clamped to the margins.
+        write!(screen, "\x1b[9;9HQ").unwrap();
+        assert_eq!(screen.text(), "aaaaa\nbWccb\nc  Qc\nddddd");
+
+        // Leaving origin mode addresses the whole screen again.
+        write!(screen, "\x1b[?6l\x1b[1;1HR").unwrap();
+        assert_eq!(screen.text(), "Raaaa\nbWccb\nc  Qc\nddddd");
+    }
+
+    #[test]
+    fn repeat() {
+        let mut screen = screen(4, 2);
+        // Nothing has been printed yet so there is nothing to repeat.
+        write!(screen, "\x1b[3b").unwrap();
+        assert_eq!(screen.text(), "\n");
+
+        write!(screen, "\x1b[1mab\x1b[3b").unwrap();
+        assert_eq!(screen.text(), "abbb\nb");
+        assert_eq!(
+            screen.styled_text(),
+            "\x1b[0;1mabbb\x1b[m\n\x1b[0;1mb\x1b[m"
+        );
+    }
+
+    #[test]
+    fn rectangular_areas() {
+        let mut screen = screen(6, 3);
+        write!(screen, "abcdef\r\nghijkl\r\nmnopqr").unwrap();
+        // DECFRA fills lines 1-2, columns 2-3 with `*`.
+        write!(screen, "\x1b[42;1;2;2;3$x").unwrap();
+        assert_eq!(screen.text(), "a**def\ng**jkl\nmnopqr");
+        // DECERA erases line 3, columns 5-6.
+        write!(screen, "\x1b[3;5;3;6$z").unwrap();
+        assert_eq!(screen.text(), "a**def\ng**jkl\nmnop");
+        // DECCRA copies line 1, columns 5-6 to line 3, column 1.
+        write!(screen, "\x1b[1;5;1;6;1;3;1;1$v").unwrap();
+        assert_eq!(screen.text(), "a**def\ng**jkl\nefop");
+    }
+
+    #[test]
+    fn rectangular_attributes() {
+        let mut screen = screen(4, 3);
+        write!(screen, "abcd\r\nefgh\r\nijkl").unwrap();
+        // By default DECCARA changes the attributes of the stream of characters from line 1,
+        // column 2 to line 2, column 3.
+        write!(screen, "\x1b[1;2;2;3;1$r").unwrap();
+        assert_eq!(
+            screen.styled_text(),
+            "a\x1b[0;1mbcd\x1b[m\n\x1b[0;1mefg\x1b[0mh\nijkl"
+        );
+
+        // With DECSACE 2 DECRARA reverses the attributes of the rectangle of lines 2-3 and
+        // columns 1-2 instead.
+        write!(screen, "\x1b[2*x\x1b[2;1;3;2;1$t").unwrap();
+        assert_eq!(
+            screen.styled_text(),
+            "a\x1b[0;1mbcd\x1b[m\nef\x1b[0;1mg\x1b[0mh\n\x1b[0;1mij\x1b[0mkl"
+        );
+
+        // DECSACE 1 switches back to the stream extent.
+        write!(screen, "\x1b[1*x\x1b[1;3;2;3;1$t").unwrap();
+        assert_eq!(
+            screen.styled_text(),
+            "a\x1b[0;1mb\x1b[0mcd\n\x1b[0;1mef\x1b[0mgh\n\x1b[0;1mij\x1b[0mkl"
+        );
+        assert_eq!(screen.text(), "abcd\nefgh\nijkl");
+    }
+
+    #[test]
+    fn editing_within_margins() {
+        let mut screen = screen(6, 1);
+        write!(screen, "abcdef\x1b[?69h\x1b[2;5s").unwrap();
+        // Characters are inserted and deleted between the cursor and the right margin.
+        write!(screen, "\x1b[1;3H\x1b[2@").unwrap();
+        assert_eq!(screen.text(), "ab  cf");
+        write!(screen, "\x1b[1;2H\x1b[P").unwrap();
+        assert_eq!(screen.text(), "a  c f");
+        // Outside of the margins the edit applies up to the last column.
+        write!(screen, "\x1b[1;6H\x1b[@").unwrap();
+        assert_eq!(screen.text(), "a  c");
+    }
+
+    #[test]
+    fn resize_alternate_screen() {
+        let mut screen = screen(4, 2);
+        write!(screen, "main\x1b[?1049h\x1b[Halt").unwrap();
+        screen.resize(WindowSize {
+            cols: 6,
+            rows: 3,
+            pixel_width: None,
+            pixel_height: None,
+        });
+        assert_eq!(screen.text(), "alt\n\n");
+        write!(screen, "\x1b[3;1Hbottom").unwrap();
+        assert_eq!(screen.text(), "alt\n\nbottom");
+
+        // The primary screen was resized as well.
+        write!(screen, "\x1b[?1049l").unwrap();
+        assert_eq!(screen.text(), "main\n\n");
+
+        write!(screen, "\x1b[?1049h\x1b[Halt").unwrap();
+        screen.resize(WindowSize {
+            cols: 2,
+            rows: 1,
+            pixel_width: None,
+            pixel_height: None,
+        });
+        assert_eq!(screen.text(), "al");
+        write!(screen, "\x1b[?1049l").unwrap();
+        assert_eq!(screen.text(), "ma");
+    }
+}
@@ -4,6 +4,13 @@ mod unix;
 #[cfg(windows)]
 mod windows;
 
+mod capabilities;
+mod mode;
+pub(crate) mod query;
+mod session;
+mod synchronized;
+mod virtual_terminal;
+
 use std::{io, time::Duration};
 
 #[cfg(unix)]
@@ -12,6 +19,13 @@ pub use unix::*;
 #[cfg(windows)]
 pub use windows::*;
 
+pub use capabilities::Capabilities;
+pub use mode::TerminalMode;
+pub use query::{Query, QueryReply};
+pub use session::{Session, SessionBuilder};
+pub use synchronized::{SynchronizedUpdate, SynchronizedWriter};
+pub use virtual_terminal::VirtualTerminal;
+
 use crate::{Event, EventReader, WindowSize};
 
 /// An alias to the terminal available for the current platform.
@@ -69,4 +83,58 @@ pub trait Terminal: io::Write {
     /// equivalent which implements `std::io::Write`. When the hook function is finished running
     /// the handle's modes will be reset (same as `enter_cooked_mode`).
     fn set_panic_hook(&mut self, f: impl Fn(&mut PlatformHandle) + Send + Sync + 'static);
+    /// Sends the queries to the terminal and waits at most `timeout` for their replies.
+    ///
+    /// The queries are followed by a request for the primary device attributes which nearly all
+    /// terminals answer. Once that reply arrives any query without a reply is considered
+    /// [QueryReply::Unsupported]. The replies are returned in the same order as the queries.
+    ///
+    /// Events which are not replies to these queries are left in the terminal's event reader.
+    /// The terminal should be in raw mode while querying.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use termina::{PlatformTerminal, Query, QueryReply, Terminal as _};
+    /// let mut terminal = PlatformTerminal::new()?;
+    /// terminal.enter_raw_mode()?;
+    /// let replies = terminal.query(
+    ///     &[Query::CursorPosition, Query::BackgroundColor],
+    ///     Duration::from_millis(100),
+    /// )?;
+    /// if let QueryReply::BackgroundColor(color) = replies[1] {
+    ///     // ...
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    fn query(&mut self, queries: &[Query], timeout: Duration) -> io::Result<Vec<QueryReply>> {
+        query::query(self, queries, timeout)
+    }
+    /// Reads the dimensions of the terminal, asking the terminal for the pixel size if the
+    /// operating system doesn't report it.
+    ///
+    /// This is the same as [Self::get_dimensions] when the pixel size is known. Otherwise the
+    /// pixel size of the text area is queried with [Query::TextAreaSizePixels], falling back to
+    /// [Query::CellSizePixels] multiplied by the number of rows and columns. The reply is cached
+    /// until the event reader reads the next `Event::WindowResized`, so calling this again is
+    /// cheap. The pixel size stays `None` if the terminal doesn't reply before the timeout.
+    ///
+    /// Like [Self::query] the terminal should be in raw mode.
+    fn query_dimensions(&mut self, timeout: Duration) -> io::Result<WindowSize> {
+        query::query_dimensions(self, timeout)
+    }
+    /// Suspends the process as if the user pressed Ctrl+Z in a shell.
+    ///
+    /// In raw mode the terminal doesn't turn Ctrl+Z into a SIGTSTP signal so applications which
+    /// support job control should call this when they read that key instead. The terminal is
+    /// returned to cooked mode while the process is stopped and raw mode is re-applied when the
+    /// process is continued. An `Event::Resumed` is emitted once the process continues.
+    ///
+    /// This function returns once the process has been continued. Job control is not supported
+    /// on Windows and the default implementation returns an `Unsupported` error.
+    fn suspend(&mut self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "job control is not supported by this terminal",
+        ))
+    }
 }
@@ -0,0 +1,269 @@
+// Capability detection sends one batch of queries, fenced by the primary device attributes like
+// any other batch (see `query.rs`).
+//
+// True color and underline support is detected by setting SGRs and then asking the terminal which
+// SGRs are active with DECRQSS. A terminal which doesn't support an SGR doesn't echo it back:
+// <https://github.com/termstandard/colors?tab=readme-ov-file#querying-the-terminal>
+
+use std::{io, time::Duration};
+
+use crate::{
+    escape::csi::{self, Csi},
+    style::{RgbColor, Underline},
+    Query, QueryReply,
+};
+
+use super::Terminal;
+
+const TEST_COLOR: RgbColor = RgbColor::new(150, 150, 150);
+
+const fn mode(code: csi::DecPrivateModeCode) -> Query {
+    Query::DecPrivateMode(csi::DecPrivateMode::Code(code))
+}
+
+const QUERIES: [Query; 9] = [
+    Query::KittyKeyboardFlags,
+    mode(csi::DecPrivateModeCode::SynchronizedOutput),
+    mode(csi::DecPrivateModeCode::GraphemeClustering),
+    mode(csi::DecPrivateModeCode::Theme),
+    mode(csi::DecPrivateModeCode::InBandResize),
+    Query::GraphicRendition,
+    Query::TerminalNameAndVersion,
+    Query::KittyGraphics,
+    Query::DeviceAttributes,
+];
+
+/// Features supported by the terminal, as found by [Capabilities::detect].
+///
+/// Every feature is `false` (or `None`) unless the terminal replied positively to the query for
+/// that feature.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The Kitty keyboard protocol. See `csi::Keyboard`.
+    pub kitty_keyboard: bool,
+    /// Synchronized output with `csi::DecPrivateModeCode::SynchronizedOutput` (mode 2026).
+    pub synchronized_output: bool,
+    /// 24-bit RGB colors.
+    pub true_color: bool,
+    /// Underline styles other than single and double, like `Underline::Curly`.
+    pub styled_underlines: bool,
+    /// Setting the color of underlines with `csi::Sgr::UnderlineColor`.
+    pub colored_underlines: bool,
+    /// Sixel graphics, as reported in the device attributes.
+    pub sixel: bool,
+    /// The Kitty graphics protocol. See `Apc::KittyGraphics`.
+    pub kitty_graphics: bool,
+    /// Access to the clipboard with `Osc::SetSelection` (OSC 52), as reported in the device
+    /// attributes.
+    ///
+    /// Few terminals report this attribute so a `false` value doesn't necessarily mean that the
+    /// terminal doesn't support OSC 52.
+    pub clipboard: bool,
+    /// Resize notifications with `csi::DecPrivateModeCode::InBandResize` (mode 2048).
+    pub in_band_resize: bool,
+    /// Light/dark theme notifications with `csi::DecPrivateModeCode::Theme` (mode 2031).
+    pub theme_reporting: bool,
+    /// Grapheme clustering with `csi::DecPrivateModeCode::GraphemeClustering` (mode 2027).
+    pub grapheme_clustering: bool,
+    /// The terminal's name from its reply to `csi::Device::RequestTerminalNameAndVersion`.
+    pub terminal_name: Option<String>,
+    /// The terminal's version from its reply to `csi::Device::RequestTerminalNameAndVersion`.
+    pub terminal_version: Option<String>,
+    /// The terminal's reply to `csi::Device::RequestPrimaryDeviceAttributes`.
+    ///
+    /// When this is `None` the terminal didn't reply before the timeout and the other fields may
+    /// be incomplete.
+    pub device_attributes: Option<csi::DeviceAttributes>,
+}
+
+impl Capabilities {
+    /// A timeout for [Self::detect] which is long enough for most local terminals. Consider a
+    /// longer timeout over SSH.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// Queries the terminal for its capabilities, waiting at most `timeout` for all replies.
+    ///
+    /// Events which are not replies to these queries, like key presses which arrive while
+    /// detecting, are left in the terminal's event reader to be read later. The terminal should
+    /// be in raw mode while querying so that the replies are not echoed.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use termina::{Capabilities, PlatformTerminal, Terminal as _};
+    /// let mut terminal = PlatformTerminal::new()?;
+    /// terminal.enter_raw_mode()?;
+    /// let capabilities = Capabilities::detect(&mut terminal, Duration::from_millis(200))?;
+    /// if capabilities.kitty_keyboard {
+    ///     // ...
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn detect<T: Terminal>(terminal: &mut T, timeout: Duration) -> io::Result<Self> {
+        write!(
+            terminal,
+            "{}{}{}",
+            Csi::Sgr(csi::Sgr::Background(TEST_COLOR.into())),
+            Csi::Sgr(csi::Sgr::UnderlineColor(TEST_COLOR.into())),
+            Csi::Sgr(csi::Sgr::Underline(Underline::Curly)),
+        )?;
+        let replies = terminal.query(&QUERIES, timeout);
+        write!(terminal, "{}", Csi::Sgr(csi::Sgr::Reset))?;
+        terminal.flush()?;
+
+        let mut capabilities = Self::default();
+        for (query, reply) in QUERIES.iter().zip(replies?) {
+            capabilities.update(query, reply);
+        }
+        Ok(capabilities)
+    }
+
+    fn update(&mut self, query: &Query, reply: QueryReply) {
+        match (query, reply) {
+            (_, QueryReply::KittyKeyboardFlags(_)) => self.kitty_keyboard = true,
+            (
+                Query::DecPrivateMode(csi::DecPrivateMode::Code(mode)),
+                QueryReply::DecPrivateMode(setting),
+            ) => {
+                let supported = setting != csi::DecModeSetting::NotRecognized;
+                match mode {
+                    csi::DecPrivateModeCode::SynchronizedOutput => {
+                        self.synchronized_output = supported
+                    }
+                    csi::DecPrivateModeCode::GraphemeClustering => {
+                        self.grapheme_clustering = supported
+                    }
+                    csi::DecPrivateModeCode::Theme => self.theme_reporting = supported,
+                    csi::DecPrivateModeCode::InBandResize => self.in_band_resize = supported,
+                    _ => (),
+                }
+            }
+            (_, QueryReply::GraphicRendition(sgrs)) => {
+                self.true_color = sgrs.contains(&csi::Sgr::Background(TEST_COLOR.into()));
+                self.colored_underlines =
+                    sgrs.contains(&csi::Sgr::UnderlineColor(TEST_COLOR.into()));
+                self.styled_underlines = sgrs.contains(&csi::Sgr::Underline(Underline::Curly));
+            }
+            (_, QueryReply::TerminalNameAndVersion(text)) => {
+                let (name, version) = split_name_and_version(&text);
+                self.terminal_name = Some(name.to_string());
+                self.terminal_version = version.map(str::to_string);
+            }
+            (_, QueryReply::KittyGraphics(supported)) => self.kitty_graphics = supported,
+            (_, QueryReply::DeviceAttributes(attributes)) => {
+                self.sixel = attributes
+                    .attributes
+                    .contains(csi::DeviceAttributeFlags::SIXEL_GRAPHICS);
+                self.clipboard = attributes
+                    .attributes
+                    .contains(csi::DeviceAttributeFlags::CLIPBOARD_ACCESS);
+                self.device_attributes = Some(attributes);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Splits an XTVERSION reply like `kitty(0.36.4)` or `tmux 3.4` into the name and version.
+fn split_name_and_version(text: &str) -> (&str, Option<&str>) {
+    if let Some((name, version)) = text.strip_suffix(')').and_then(|text| text.split_once('(')) {
+        return (name.trim_end(), Some(version));
+    }
+    match text.split_once(' ') {
+        Some((name, version)) => (name, Some(version)),
+        None => (text, None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{VirtualTerminal, WindowSize};
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn detect() {
+        let mut terminal = VirtualTerminal::new(WindowSize::TEST);
+        terminal.input().send_bytes(
+            b"\x1b[?1u\
+            \x1b[?2026;2$y\x1b[?2027;0$y\x1b[?2031;2$y\x1b[?2048;2$y\
+            \x1bP1$r0;48:2::150:150:150;58:2::150:150:150;4:3m\x1b\\\
+            \x1bP>|kitty(0.36.4)\x1b\\\
+            \x1b_Gi=31;OK\x1b\\\
+            \x1b[?62;4;52c",
+        );
+        let capabilities = Capabilities::detect(&mut terminal, TIMEOUT).unwrap();
+        assert_eq!(
+            capabilities,
+            Capabilities {
+                kitty_keyboard: true,
+                synchronized_output: true,
+                true_color: true,
+                styled_underlines: true,
+                colored_underlines: true,
+                sixel: true,
+                kitty_graphics: true,
+                clipboard: true,
+                in_band_resize: true,
+                theme_reporting: true,
+                grapheme_clustering: false,
+                terminal_name: Some("kitty".to_string()),
+                terminal_version: Some("0.36.4".to_string()),
+                device_attributes: Some(csi::DeviceAttributes {
+                    conformance_level: 62,
+                    attributes: csi::DeviceAttributeFlags::SIXEL_GRAPHICS
+                        | csi::DeviceAttributeFlags::CLIPBOARD_ACCESS,
+                }),
+            }
+        );
+        // The test SGRs are reset after the queries.
+        let output = String::from_utf8(terminal.take_output()).unwrap();
+        let background = Csi::Sgr(csi::Sgr::Background(TEST_COLOR.into())).to_string();
+        assert!(output.starts_with(&background));
+        assert!(output.ends_with(&format!("\x1b[c{}", Csi::Sgr(csi::Sgr::Reset))));
+    }
+
+    #[test]
+    fn unknown_terminal() {
+        // The terminal only answers the fence.
+        let mut terminal = VirtualTerminal::new(WindowSize::TEST);
+        terminal.input().send_bytes(b"\x1b[?62;22c");
+        let capabilities = Capabilities::detect(&mut terminal, TIMEOUT).unwrap();
+        assert_eq!(
+            capabilities,
+            Capabilities {
+                device_attributes: Some(csi::DeviceAttributes {
+                    conformance_level: 62,
+                    attributes: csi::DeviceAttributeFlags::ANSI_COLOR,
+                }),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn timed_out() {
+        // Replies which arrive before the timeout are kept.
+        let mut terminal = VirtualTerminal::new(WindowSize::TEST);
+        terminal.input().send_bytes(b"\x1b[?1u");
+        let capabilities = Capabilities::detect(&mut terminal, Duration::ZERO).unwrap();
+        assert_eq!(
+            capabilities,
+            Capabilities {
+                kitty_keyboard: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn name_and_version() {
+        assert_eq!(
+            split_name_and_version("kitty(0.36.4)"),
+            ("kitty", Some("0.36.4"))
+        );
+        assert_eq!(split_name_and_version("tmux 3.4"), ("tmux", Some("3.4")));
+        assert_eq!(split_name_and_version("foot"), ("foot", None));
+    }
+}
@@ -0,0 +1,209 @@
+// Line disciplines between "raw" and "cooked". The names follow the curses functions which set
+// similar modes (`cbreak`, `raw`, `noecho`) and the termios flags they control. See termios(3)
+// and <https://learn.microsoft.com/en-us/windows/console/setconsolemode> for the details of each
+// flag.
+
+use std::time::Duration;
+
+/// The input and output processing of a terminal, set with `set_mode` on `UnixTerminal` or
+/// `WindowsTerminal`.
+///
+/// A mode is built from one of [Self::raw], [Self::cbreak] or [Self::cooked] and then adjusted:
+///
+/// ```
+/// # use termina::TerminalMode;
+/// // Raw input, but `\n` written to the terminal still moves to the start of the next line.
+/// let mode = TerminalMode::raw().output_processing(true);
+/// assert!(!mode.is_canonical());
+/// ```
+///
+/// On Unix each setting corresponds to termios flags. Windows consoles have equivalents for all
+/// settings except the [read minimum](Self::read_minimum) and [read
+/// timeout](Self::read_timeout), which are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalMode {
+    echo: bool,
+    canonical: bool,
+    signals: bool,
+    output_processing: bool,
+    read_minimum: u8,
+    /// In tenths of a second, like `VTIME`.
+    read_timeout: u8,
+}
+
+impl TerminalMode {
+    /// The mode set by `Terminal::enter_raw_mode`: input is passed to the application byte by
+    /// byte without echoing it or interpreting any keys and output is written unchanged.
+    pub const fn raw() -> Self {
+        Self {
+            echo: false,
+            canonical: false,
+            signals: false,
+            output_processing: false,
+            read_minimum: 1,
+            read_timeout: 0,
+        }
+    }
+
+    /// Like [Self::raw] except that keys which send signals like Ctrl+C still send them and
+    /// output is processed.
+    ///
+    /// This suits line-oriented tools which read keys one at a time but otherwise print like any
+    /// other command line program.
+    pub const fn cbreak() -> Self {
+        Self {
+            signals: true,
+            output_processing: true,
+            ..Self::raw()
+        }
+    }
+
+    /// The usual mode for a terminal: input is echoed and passed to the application a line at a
+    /// time.
+    pub const fn cooked() -> Self {
+        Self {
+            echo: true,
+            canonical: true,
+            signals: true,
+            output_processing: true,
+            ..Self::raw()
+        }
+    }
+
+    /// Whether the terminal echoes input (`ECHO`, `ENABLE_ECHO_INPUT`).
+    ///
+    /// Windows consoles only echo in [canonical](Self::canonical) mode.
+    pub const fn echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    /// Whether input is buffered until the end of the line and can be edited with keys like
+    /// Backspace (`ICANON`, `ENABLE_LINE_INPUT`).
+    pub const fn canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    /// Whether keys like Ctrl+C and Ctrl+Z send signals instead of being passed to the
+    /// application (`ISIG`, `ENABLE_PROCESSED_INPUT`).
+    pub const fn signals(mut self, signals: bool) -> Self {
+        self.signals = signals;
+        self
+    }
+
+    /// Whether output is post-processed, most notably so that writing `\n` also returns the
+    /// cursor to the first column (`OPOST`, the absence of `DISABLE_NEWLINE_AUTO_RETURN`).
+    pub const fn output_processing(mut self, output_processing: bool) -> Self {
+        self.output_processing = output_processing;
+        self
+    }
+
+    /// The minimum number of bytes for a read of the terminal to complete in non-canonical mode
+    /// (`VMIN`). The default is 1.
+    ///
+    /// Together with [Self::read_timeout] this controls when input is considered available,
+    /// which affects the terminal's event reader as well as any other reader of the terminal.
+    pub const fn read_minimum(mut self, bytes: u8) -> Self {
+        self.read_minimum = bytes;
+        self
+    }
+
+    /// The time to wait for input in non-canonical mode (`VTIME`). The default is zero.
+    ///
+    /// The timeout has a resolution of a tenth of a second and is at most 25.5 seconds. Longer
+    /// timeouts are clamped.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = (timeout.as_millis() / 100).min(u8::MAX as u128) as u8;
+        self
+    }
+
+    pub const fn is_echo(&self) -> bool {
+        self.echo
+    }
+
+    pub const fn is_canonical(&self) -> bool {
+        self.canonical
+    }
+
+    pub const fn has_signals(&self) -> bool {
+        self.signals
+    }
+
+    pub const fn has_output_processing(&self) -> bool {
+        self.output_processing
+    }
+
+    pub const fn get_read_minimum(&self) -> u8 {
+        self.read_minimum
+    }
+
+    pub const fn get_read_timeout(&self) -> Duration {
+        Duration::from_millis(self.read_timeout as u64 * 100)
+    }
+
+    /// Applies the mode to `termios`, which should be the termios the terminal started with.
+    #[cfg(unix)]
+    pub(crate) fn apply(&self, termios: &mut rustix::termios::Termios) {
+        use rustix::termios::{LocalModes, OutputModes, SpecialCodeIndex};
+
+        if !self.canonical {
+            // Besides the local modes this turns off input processing like translating `\r` to
+            // `\n` and flow control, which would otherwise swallow Ctrl+S and Ctrl+Q.
+            termios.make_raw();
+            termios.special_codes[SpecialCodeIndex::VMIN] = self.read_minimum;
+            termios.special_codes[SpecialCodeIndex::VTIME] = self.read_timeout;
+        }
+        termios.local_modes.set(LocalModes::ICANON, self.canonical);
+        termios.local_modes.set(LocalModes::ECHO, self.echo);
+        termios.local_modes.set(LocalModes::ISIG, self.signals);
+        termios
+            .output_modes
+            .set(OutputModes::OPOST, self.output_processing);
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn from_termios(termios: &rustix::termios::Termios) -> Self {
+        use rustix::termios::{LocalModes, OutputModes, SpecialCodeIndex};
+
+        Self {
+            echo: termios.local_modes.contains(LocalModes::ECHO),
+            canonical: termios.local_modes.contains(LocalModes::ICANON),
+            signals: termios.local_modes.contains(LocalModes::ISIG),
+            output_processing: termios.output_modes.contains(OutputModes::OPOST),
+            read_minimum: termios.special_codes[SpecialCodeIndex::VMIN],
+            read_timeout: termios.special_codes[SpecialCodeIndex::VTIME],
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn termios_round_trip() {
+        let (_master, subsidiary) = crate::pty::open(crate::WindowSize::TEST).unwrap();
+        let original = rustix::termios::tcgetattr(&subsidiary).unwrap();
+
+        for mode in [
+            TerminalMode::raw(),
+            TerminalMode::cbreak(),
+            TerminalMode::raw()
+                .signals(true)
+                .read_minimum(0)
+                .read_timeout(Duration::from_millis(500)),
+        ] {
+            let mut termios = original.clone();
+            mode.apply(&mut termios);
+            rustix::termios::tcsetattr(
+                &subsidiary,
+                rustix::termios::OptionalActions::Now,
+                &termios,
+            )
+            .unwrap();
+            let termios = rustix::termios::tcgetattr(&subsidiary).unwrap();
+            assert_eq!(TerminalMode::from_termios(&termios), mode);
+        }
+    }
+}
@@ -0,0 +1,510 @@
+// Queries are sent in batches followed by a request for the primary device attributes (DA1).
+// Practically every terminal answers DA1 and terminals answer queries in order, so once the DA1
+// reply arrives every query which the terminal understood has been answered. Queries which the
+// terminal doesn't understand are ignored and so they just never get a reply.
+//
+// The DA1 reply to a batch which timed out may arrive while a later batch waits, so the event
+// reader counts the DA1 requests and replies and a batch waits for the reply matching its own
+// request. See `EventReader::request_fence`.
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    escape::{
+        apc::Apc,
+        csi::{self, Csi, KittyKeyboardFlags},
+        dcs::{self, Dcs},
+        osc::Osc,
+    },
+    style::RgbColor,
+    Event, EventReader, OneBased, WindowSize,
+};
+
+use super::Terminal;
+
+/// The image ID used by [Query::KittyGraphics].
+const KITTY_GRAPHICS_QUERY_ID: &str = "i=31";
+
+/// A request for information from the terminal. See [Terminal::query].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// The cursor position, with `csi::Cursor::RequestActivePositionReport`.
+    CursorPosition,
+    /// The setting of a DEC private mode, with `csi::Mode::QueryDecPrivateMode`.
+    DecPrivateMode(csi::DecPrivateMode),
+    /// The current Kitty keyboard protocol flags, with `csi::Keyboard::QueryFlags`.
+    KittyKeyboardFlags,
+    /// The default foreground color, with `Osc::QueryForegroundColor`.
+    ForegroundColor,
+    /// The default background color, with `Osc::QueryBackgroundColor`.
+    BackgroundColor,
+    /// The value of a terminfo capability, with `Dcs::RequestTerminfoCapabilities`.
+    TerminfoCapability(String),
+    /// The name and version of the terminal, with `csi::Device::RequestTerminalNameAndVersion`.
+    TerminalNameAndVersion,
+    /// The size of the text area in pixels, with `csi::Window::ReportTextAreaSizePixels`.
+    TextAreaSizePixels,
+    /// The size of a cell in pixels, with `csi::Window::ReportCellSizePixels`.
+    CellSizePixels,
+    /// The active SGRs, with `Dcs::Request(DcsRequest::GraphicRendition)` (DECRQSS). A terminal
+    /// only reports the SGRs which it supports.
+    GraphicRendition,
+    /// Support for the Kitty graphics protocol, by querying with a 1x1 image with
+    /// `Apc::KittyGraphics`.
+    KittyGraphics,
+    /// The primary device attributes. Nothing is written for this query: the reply to the DA1
+    /// fence is used.
+    DeviceAttributes,
+}
+
+/// The terminal's reply to a [Query].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryReply {
+    CursorPosition {
+        line: OneBased,
+        col: OneBased,
+    },
+    DecPrivateMode(csi::DecModeSetting),
+    KittyKeyboardFlags(KittyKeyboardFlags),
+    ForegroundColor(RgbColor),
+    BackgroundColor(RgbColor),
+    /// The value of the capability or `None` for a boolean capability.
+    TerminfoCapability(Option<String>),
+    TerminalNameAndVersion(String),
+    TextAreaSizePixels {
+        width: u16,
+        height: u16,
+    },
+    CellSizePixels {
+        width: u16,
+        height: u16,
+    },
+    GraphicRendition(Vec<csi::Sgr>),
+    /// Whether the terminal accepted the image rather than replying with an error.
+    KittyGraphics(bool),
+    DeviceAttributes(csi::DeviceAttributes),
+    /// The terminal replied to the fence without replying to the query.
+    Unsupported,
+    /// The terminal didn't reply to the fence before the timeout.
+    TimedOut,
+}
+
+impl Query {
+    fn write_request(&self, w: &mut (impl io::Write + ?Sized)) -> io::Result<()> {
+        match self {
+            Self::CursorPosition => write!(
+                w,
+                "{}",
+                Csi::Cursor(csi::Cursor::RequestActivePositionReport)
+            ),
+            Self::DecPrivateMode(mode) => {
+                write!(w, "{}", Csi::Mode(csi::Mode::QueryDecPrivateMode(*mode)))
+            }
+            Self::KittyKeyboardFlags => write!(w, "{}", Csi::Keyboard(csi::Keyboard::QueryFlags)),
+            Self::ForegroundColor => write!(w, "{}", Osc::QueryForegroundColor),
+            Self::BackgroundColor => write!(w, "{}", Osc::QueryBackgroundColor),
+            Self::TerminfoCapability(name) => write!(
+                w,
+                "{}",
+                Dcs::RequestTerminfoCapabilities(vec![name.clone()])
+            ),
+            Self::TerminalNameAndVersion => write!(
+                w,
+                "{}",
+                Csi::Device(csi::Device::RequestTerminalNameAndVersion)
+            ),
+            Self::TextAreaSizePixels => write!(
+                w,
+                "{}",
+                Csi::Window(Box::new(csi::Window::ReportTextAreaSizePixels))
+            ),
+            Self::CellSizePixels => write!(
+                w,
+                "{}",
+                Csi::Window(Box::new(csi::Window::ReportCellSizePixels))
+            ),
+            Self::GraphicRendition => {
+                write!(w, "{}", Dcs::Request(dcs::DcsRequest::GraphicRendition))
+            }
+            Self::KittyGraphics => write!(
+                w,
+                "{}",
+                Apc::KittyGraphics {
+                    control_data: format!("{KITTY_GRAPHICS_QUERY_ID},s=1,v=1,a=q,t=d,f=24"),
+                    payload: "AAAA".to_string(),
+                }
+            ),
+            // The fence is the request.
+            Self::DeviceAttributes => Ok(()),
+        }
+    }
+
+    /// Returns the reply to this query if the event is one.
+    fn reply(&self, event: &Event) -> Option<QueryReply> {
+        let reply = match (self, event) {
+            (
+                Self::CursorPosition,
+                Event::Csi(Csi::Cursor(csi::Cursor::ActivePositionReport { line, col })),
+            ) => QueryReply::CursorPosition {
+                line: *line,
+                col: *col,
+            },
+            (
+                Self::DecPrivateMode(mode),
+                Event::Csi(Csi::Mode(csi::Mode::ReportDecPrivateMode {
+                    mode: reported,
+                    setting,
+                })),
+            ) if mode == reported => QueryReply::DecPrivateMode(*setting),
+            (
+                Self::KittyKeyboardFlags,
+                Event::Csi(Csi::Keyboard(csi::Keyboard::ReportFlags(flags))),
+            ) => QueryReply::KittyKeyboardFlags(*flags),
+            (Self::ForegroundColor, Event::Osc(Osc::ReportForegroundColor(color))) => {
+                QueryReply::ForegroundColor(*color)
+            }
+            (Self::BackgroundColor, Event::Osc(Osc::ReportBackgroundColor(color))) => {
+                QueryReply::BackgroundColor(*color)
+            }
+            (
+                Self::TerminfoCapability(name),
+                Event::Dcs(Dcs::TerminfoCapability {
+                    is_request_valid,
+                    name: reported,
+                    value,
+                }),
+            ) => {
+                // Some terminals leave out the name when the request is invalid.
+                let unnamed_invalid = reported.is_empty() && !is_request_valid;
+                if reported != name && !unnamed_invalid {
+                    return None;
+                }
+                if *is_request_valid {
+                    QueryReply::TerminfoCapability(value.clone())
+                } else {
+                    QueryReply::Unsupported
+                }
+            }
+            (Self::TerminalNameAndVersion, Event::Dcs(Dcs::TerminalNameAndVersion(text))) => {
+                QueryReply::TerminalNameAndVersion(text.clone())
+            }
+            (Self::TextAreaSizePixels, Event::Csi(Csi::Window(window))) => match **window {
+                csi::Window::ReportTextAreaSizePixelsResponse {
+                    width: Some(width),
+                    height: Some(height),
+                } => QueryReply::TextAreaSizePixels {
+                    width: width.try_into().ok()?,
+                    height: height.try_into().ok()?,
+                },
+                _ => return None,
+            },
+            (Self::CellSizePixels, Event::Csi(Csi::Window(window))) => match **window {
+                csi::Window::ReportCellSizePixelsResponse {
+                    width: Some(width),
+                    height: Some(height),
+                } => QueryReply::CellSizePixels {
+                    width: width.try_into().ok()?,
+                    height: height.try_into().ok()?,
+                },
+                _ => return None,
+            },
+            (
+                Self::GraphicRendition,
+                Event::Dcs(Dcs::Response {
+                    is_request_valid,
+                    value: dcs::DcsResponse::GraphicRendition(sgrs),
+                }),
+            ) => {
+                if *is_request_valid {
+                    QueryReply::GraphicRendition(sgrs.clone())
+                } else {
+                    QueryReply::Unsupported
+                }
+            }
+            (
+                Self::KittyGraphics,
+                Event::Apc(Apc::KittyGraphics {
+                    control_data,
+                    payload,
+                }),
+            ) if control_data
+                .split(',')
+                .any(|kv| kv == KITTY_GRAPHICS_QUERY_ID) =>
+            {
+                QueryReply::KittyGraphics(payload == "OK")
+            }
+            _ => return None,
+        };
+        Some(reply)
+    }
+}
+
+fn is_fence(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Csi(Csi::Device(csi::Device::DeviceAttributes(_)))
+    )
+}
+
+/// Writes the queries followed by the DA1 fence and flushes the writer.
+pub(crate) fn write_requests(
+    w: &mut (impl io::Write + ?Sized),
+    queries: &[Query],
+) -> io::Result<()> {
+    for query in queries {
+        query.write_request(w)?;
+    }
+    write!(
+        w,
+        "{}",
+        Csi::Device(csi::Device::RequestPrimaryDeviceAttributes)
+    )?;
+    w.flush()
+}
+
+/// Whether the event is a reply to one of the queries or the fence.
+pub(crate) fn is_reply(queries: &[Query], event: &Event) -> bool {
+    is_fence(event) || queries.iter().any(|query| query.reply(event).is_some())
+}
+
+/// Collects the replies to queries written with [write_requests].
+#[derive(Debug)]
+pub(crate) struct Replies {
+    replies: Vec<Option<QueryReply>>,
+    reader: EventReader,
+    /// The number of the DA1 reply which ends the batch. See `EventReader::request_fence`.
+    fence: u64,
+}
+
+impl Replies {
+    /// Starts collecting replies for queries which were just written to the terminal of
+    /// `reader`.
+    pub(crate) fn new(reader: &EventReader, queries: &[Query]) -> Self {
+        Self {
+            replies: vec![None; queries.len()],
+            reader: reader.clone(),
+            fence: reader.request_fence(),
+        }
+    }
+
+    /// Records an event accepted by [is_reply], returning whether it is the batch's fence.
+    pub(crate) fn push(&mut self, queries: &[Query], event: &Event) -> bool {
+        if is_fence(event) {
+            if !self.reader.receive_fence(self.fence) {
+                return false;
+            }
+            if let Event::Csi(Csi::Device(csi::Device::DeviceAttributes(attributes))) = event {
+                for (query, slot) in queries.iter().zip(self.replies.iter_mut()) {
+                    if *query == Query::DeviceAttributes {
+                        *slot = Some(QueryReply::DeviceAttributes(*attributes));
+                    }
+                }
+            }
+            return true;
+        }
+        // The terminal replies in the order of the queries so give the reply to the first
+        // unanswered query which accepts it.
+        if let Some((slot, reply)) = queries
+            .iter()
+            .zip(self.replies.iter_mut())
+            .filter(|(_, slot)| slot.is_none())
+            .find_map(|(query, slot)| query.reply(event).map(|reply| (slot, reply)))
+        {
+            *slot = Some(reply);
+        }
+        false
+    }
+
+    pub(crate) fn finish(self, fenced: bool) -> Vec<QueryReply> {
+        let missing = if fenced {
+            QueryReply::Unsupported
+        } else {
+            QueryReply::TimedOut
+        };
+        self.replies
+            .into_iter()
+            .map(|reply| reply.unwrap_or_else(|| missing.clone()))
+            .collect()
+    }
+}
+
+pub(super) fn query<T: Terminal + ?Sized>(
+    terminal: &mut T,
+    queries: &[Query],
+    timeout: Duration,
+) -> io::Result<Vec<QueryReply>> {
+    let deadline = Instant::now() + timeout;
+    write_requests(terminal, queries)?;
+
+    let filter = |event: &Event| is_reply(queries, event);
+    let mut replies = Replies::new(&terminal.event_reader(), queries);
+    let fenced = loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if !terminal.poll(filter, Some(timeout))? {
+            break false;
+        }
+        let event = terminal.read(filter)?;
+        if replies.push(queries, &event) {
+            break true;
+        }
+    };
+
+    Ok(replies.finish(fenced))
+}
+
+pub(super) fn query_dimensions<T: Terminal + ?Sized>(
+    terminal: &mut T,
+    timeout: Duration,
+) -> io::Result<WindowSize> {
+    let mut size = terminal.get_dimensions()?;
+    if size.pixel_width.is_some() && size.pixel_height.is_some() {
+        return Ok(size);
+    }
+
+    let reader = terminal.event_reader();
+    let (resizes, cached) = reader.cached_pixel_size();
+    let pixel_size = match cached {
+        Some(pixel_size) => Some(pixel_size),
+        None => {
+            let replies = query(
+                terminal,
+                &[Query::TextAreaSizePixels, Query::CellSizePixels],
+                timeout,
+            )?;
+            let pixel_size = match replies.as_slice() {
+                [QueryReply::TextAreaSizePixels { width, height }, _] => Some((*width, *height)),
+                // Some terminals, for example the Linux console, only know the cell size.
+                [_, QueryReply::CellSizePixels { width, height }] => Some((
+                    width.saturating_mul(size.cols),
+                    height.saturating_mul(size.rows),
+                )),
+                _ => None,
+            };
+            if let Some(pixel_size) = pixel_size {
+                reader.cache_pixel_size(resizes, pixel_size);
+            }
+            pixel_size
+        }
+    };
+    if let Some((width, height)) = pixel_size.filter(|(width, height)| *width != 0 && *height != 0)
+    {
+        size.pixel_width = Some(width);
+        size.pixel_height = Some(height);
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::VirtualTerminal;
+
+    use super::*;
+
+    #[test]
+    fn cursor_position() {
+        let mut terminal = VirtualTerminal::new(WindowSize::TEST);
+
+        // The replies to the cursor position query and the DA1 fence.
+        terminal.input().send_bytes(b"\x1b[12;5R\x1b[?62;22c");
+        let replies = terminal
+            .query(&[Query::CursorPosition], Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(
+            replies,
+            [QueryReply::CursorPosition {
+                line: OneBased::new(12).unwrap(),
+                col: OneBased::new(5).unwrap(),
+            }]
+        );
+        assert_eq!(terminal.take_output(), b"\x1b[6n\x1b[c");
+    }
+
+    #[test]
+    fn unsupported_and_timed_out() {
+        let mut terminal = VirtualTerminal::new(WindowSize::TEST);
+        let queries = [Query::CursorPosition, Query::KittyKeyboardFlags];
+
+        // A key press arrives between the replies and stays in the reader.
+        terminal.input().send_bytes(b"\x1b[?1ua\x1b[?62;22c");
+        assert_eq!(
+            terminal.query(&queries, Duration::from_secs(1)).unwrap(),
+            [
+                QueryReply::Unsupported,
+                QueryReply::KittyKeyboardFlags(KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES),
+            ]
+        );
+        assert!(matches!(terminal.read(|_| true).unwrap(), Event::Key(_)));
+
+        assert_eq!(
+            terminal.query(&queries, Duration::ZERO).unwrap(),
+            [QueryReply::TimedOut, QueryReply::TimedOut]
+        );
+    }
+
+    #[test]
+    fn stale_fence() {
+        let mut terminal = VirtualTerminal::new(WindowSize::TEST);
+        let input = terminal.input();
+        assert_eq!(
+            terminal
+                .query(&[Query::CursorPosition], Duration::ZERO)
+                .unwrap(),
+            [QueryReply::TimedOut]
+        );
+
+        // The first fence is the late reply to the batch which timed out.
+        input.send_bytes(b"\x1b[?62;22c\x1b[12;5R\x1b[?62;22c");
+        assert_eq!(
+            terminal
+                .query(&[Query::CursorPosition], Duration::from_secs(1))
+                .unwrap(),
+            [QueryReply::CursorPosition {
+                line: OneBased::new(12).unwrap(),
+                col: OneBased::new(5).unwrap(),
+            }]
+        );
+    }
+
+    #[test]
+    fn query_dimensions_cache() {
+        let mut terminal = VirtualTerminal::new(WindowSize::TEST);
+        let input = terminal.input();
+        let with_pixels = |size: WindowSize, cell_width: u16, cell_height: u16| WindowSize {
+            pixel_width: Some(size.cols * cell_width),
+            pixel_height: Some(size.rows * cell_height),
+            ..size
+        };
+
+        // Only the cell size is supported.
+        input.send_bytes(b"\x1b[6;20;10t\x1b[?62;22c");
+        let timeout = Duration::from_secs(1);
+        assert_eq!(
+            terminal.query_dimensions(timeout).unwrap(),
+            with_pixels(WindowSize::TEST, 10, 20)
+        );
+        assert_eq!(terminal.take_output(), b"\x1b[14t\x1b[16t\x1b[c");
+        // The second call uses the cached size.
+        assert_eq!(
+            terminal.query_dimensions(timeout).unwrap(),
+            with_pixels(WindowSize::TEST, 10, 20)
+        );
+        assert!(terminal.output().is_empty());
+
+        // Reading a resize clears the cache.
+        let resized = WindowSize {
+            cols: 100,
+            ..WindowSize::TEST
+        };
+        input.resize(resized);
+        terminal.read(|_| true).unwrap();
+        input.send_bytes(b"\x1b[4;480;1000t\x1b[?62;22c");
+        assert_eq!(
+            terminal.query_dimensions(timeout).unwrap(),
+            with_pixels(resized, 10, 20)
+        );
+        assert!(!terminal.output().is_empty());
+    }
+}
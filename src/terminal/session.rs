@@ -0,0 +1,584 @@
+// Nearly every application sets up the terminal the same way and has to undo that setup in
+// reverse order when exiting, both normally and when panicking. The order matters for some
+// features: the Kitty keyboard protocol keeps separate flag stacks for the main and alternate
+// screens for example, so flags pushed after entering the alternate screen must be popped before
+// leaving it.
+
+use std::{
+    io::{self, Write as _},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    escape::{
+        csi::{self, Csi, KittyKeyboardFlags},
+        osc::Osc,
+    },
+    Event, EventReader, WindowSize,
+};
+
+use super::{PlatformHandle, Terminal};
+
+/// A feature enabled by a [Session], in the order in which features are enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Feature {
+    RawMode,
+    AlternateScreen,
+    Title(String),
+    BracketedPaste,
+    FocusTracking,
+    MouseCapture,
+    KittyKeyboard(KittyKeyboardFlags),
+}
+
+const MOUSE_MODES: [csi::DecPrivateModeCode; 5] = [
+    csi::DecPrivateModeCode::MouseTracking,
+    csi::DecPrivateModeCode::ButtonEventMouse,
+    csi::DecPrivateModeCode::AnyEventMouse,
+    csi::DecPrivateModeCode::RXVTMouse,
+    csi::DecPrivateModeCode::SGRMouse,
+];
+
+fn decset(code: csi::DecPrivateModeCode) -> Csi {
+    Csi::Mode(csi::Mode::SetDecPrivateMode(csi::DecPrivateMode::Code(
+        code,
+    )))
+}
+
+fn decreset(code: csi::DecPrivateModeCode) -> Csi {
+    Csi::Mode(csi::Mode::ResetDecPrivateMode(csi::DecPrivateMode::Code(
+        code,
+    )))
+}
+
+/// Writes the escape sequences which enable the given feature.
+fn write_setup(feature: &Feature, w: &mut impl io::Write) -> io::Result<()> {
+    match feature {
+        // Raw mode is not controlled by escape sequences.
+        Feature::RawMode => Ok(()),
+        Feature::AlternateScreen => write!(
+            w,
+            "{}",
+            decset(csi::DecPrivateModeCode::ClearAndEnableAlternateScreen)
+        ),
+        Feature::Title(title) => write!(
+            w,
+            "{}{}",
+            Csi::Window(Box::new(csi::Window::PushIconAndWindowTitle)),
+            Osc::SetIconNameAndWindowTitle(title),
+        ),
+        Feature::BracketedPaste => write!(w, "{}", decset(csi::DecPrivateModeCode::BracketedPaste)),
+        Feature::FocusTracking => write!(w, "{}", decset(csi::DecPrivateModeCode::FocusTracking)),
+        Feature::MouseCapture => {
+            for mode in MOUSE_MODES {
+                write!(w, "{}", decset(mode))?;
+            }
+            Ok(())
+        }
+        Feature::KittyKeyboard(flags) => {
+            write!(w, "{}", Csi::Keyboard(csi::Keyboard::PushFlags(*flags)))
+        }
+    }
+}
+
+/// Writes the escape sequences which undo the given features, in reverse order.
+fn write_teardown(features: &[Feature], w: &mut impl io::Write) -> io::Result<()> {
+    for feature in features.iter().rev() {
+        match feature {
+            // Raw mode is not controlled by escape sequences.
+            Feature::RawMode => (),
+            Feature::AlternateScreen => write!(
+                w,
+                "{}",
+                decreset(csi::DecPrivateModeCode::ClearAndEnableAlternateScreen)
+            )?,
+            Feature::Title(_) => write!(
+                w,
+                "{}",
+                Csi::Window(Box::new(csi::Window::PopIconAndWindowTitle))
+            )?,
+            Feature::BracketedPaste => {
+                write!(w, "{}", decreset(csi::DecPrivateModeCode::BracketedPaste))?
+            }
+            Feature::FocusTracking => {
+                write!(w, "{}", decreset(csi::DecPrivateModeCode::FocusTracking))?
+            }
+            Feature::MouseCapture => {
+                for mode in MOUSE_MODES.iter().rev() {
+                    write!(w, "{}", decreset(*mode))?;
+                }
+            }
+            Feature::KittyKeyboard(_) => {
+                write!(w, "{}", Csi::Keyboard(csi::Keyboard::PopFlags(1)))?
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A builder for a [Session].
+///
+/// Features are enabled in a fixed order regardless of the order of the builder calls:
+///
+/// 1. raw mode
+/// 2. the alternate screen
+/// 3. the window title
+/// 4. bracketed paste
+/// 5. focus tracking
+/// 6. mouse capture
+/// 7. Kitty keyboard flags
+///
+/// and disabled in reverse order.
+///
+/// ```no_run
+/// # use termina::{escape::csi::KittyKeyboardFlags, PlatformTerminal, SessionBuilder};
+/// let terminal = PlatformTerminal::new()?;
+/// let session = SessionBuilder::new()
+///     .alternate_screen(true)
+///     .bracketed_paste(true)
+///     .kitty_keyboard(KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES)
+///     .title("my app")
+///     .build(terminal)?;
+/// // Dropping the session (or panicking) restores the terminal.
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct SessionBuilder {
+    raw_mode: bool,
+    alternate_screen: bool,
+    title: Option<String>,
+    bracketed_paste: bool,
+    focus_tracking: bool,
+    mouse_capture: bool,
+    kitty_keyboard: Option<KittyKeyboardFlags>,
+    panic_hook: bool,
+}
+
+impl Default for SessionBuilder {
+    fn default() -> Self {
+        Self {
+            raw_mode: true,
+            alternate_screen: false,
+            title: None,
+            bracketed_paste: false,
+            focus_tracking: false,
+            mouse_capture: false,
+            kitty_keyboard: None,
+            panic_hook: true,
+        }
+    }
+}
+
+impl SessionBuilder {
+    /// Creates a builder which only enters raw mode and sets a panic hook.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to enter raw mode. Enabled by default.
+    pub fn raw_mode(mut self, enable: bool) -> Self {
+        self.raw_mode = enable;
+        self
+    }
+
+    /// Whether to enter the alternate screen.
+    pub fn alternate_screen(mut self, enable: bool) -> Self {
+        self.alternate_screen = enable;
+        self
+    }
+
+    /// Sets the window and icon title.
+    ///
+    /// The current title is pushed onto the terminal's title stack first and popped when the
+    /// session is restored.
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Whether to enable bracketed paste. See [Event::Paste].
+    pub fn bracketed_paste(mut self, enable: bool) -> Self {
+        self.bracketed_paste = enable;
+        self
+    }
+
+    /// Whether to enable [Event::FocusIn] and [Event::FocusOut] events.
+    pub fn focus_tracking(mut self, enable: bool) -> Self {
+        self.focus_tracking = enable;
+        self
+    }
+
+    /// Whether to enable reporting of mouse presses, drags and motion with SGR encoding.
+    pub fn mouse_capture(mut self, enable: bool) -> Self {
+        self.mouse_capture = enable;
+        self
+    }
+
+    /// Pushes the given Kitty keyboard protocol flags onto the terminal's stack.
+    pub fn kitty_keyboard(mut self, flags: KittyKeyboardFlags) -> Self {
+        self.kitty_keyboard = Some(flags);
+        self
+    }
+
+    /// Whether to restore the terminal in a panic hook. Enabled by default.
+    ///
+    /// See [Terminal::set_panic_hook].
+    pub fn panic_hook(mut self, enable: bool) -> Self {
+        self.panic_hook = enable;
+        self
+    }
+
+    /// Enables the requested features on the terminal.
+    ///
+    /// If enabling any feature fails then the features which were already enabled are disabled
+    /// again before the error is returned.
+    pub fn build<T: Terminal>(self, terminal: T) -> io::Result<Session<T>> {
+        let mut session = Session {
+            terminal,
+            features: Vec::new(),
+            active: Arc::new(AtomicBool::new(true)),
+            has_panic_hook: false,
+        };
+
+        if self.raw_mode {
+            session.terminal.enter_raw_mode()?;
+            session.features.push(Feature::RawMode);
+        }
+        if self.alternate_screen {
+            session.enable(Feature::AlternateScreen)?;
+        }
+        if let Some(title) = self.title {
+            session.enable(Feature::Title(title))?;
+        }
+        if self.bracketed_paste {
+            session.enable(Feature::BracketedPaste)?;
+        }
+        if self.focus_tracking {
+            session.enable(Feature::FocusTracking)?;
+        }
+        if self.mouse_capture {
+            session.enable(Feature::MouseCapture)?;
+        }
+        if let Some(flags) = self.kitty_keyboard {
+            session.enable(Feature::KittyKeyboard(flags))?;
+        }
+        session.terminal.flush()?;
+
+        if self.panic_hook {
+            let teardown = session.panic_teardown();
+            session.terminal.set_panic_hook(teardown);
+            session.has_panic_hook = true;
+        }
+
+        Ok(session)
+    }
+}
+
+/// A terminal with features enabled by a [SessionBuilder].
+///
+/// The session records which features were enabled and disables exactly those, in reverse order,
+/// when [Self::restore] is called, when the session is dropped or when panicking (if the
+/// session's panic hook is enabled). Restoring happens at most once.
+///
+/// This type implements [Terminal] itself so it can be used in place of the wrapped terminal.
+#[derive(Debug)]
+pub struct Session<T: Terminal> {
+    terminal: T,
+    features: Vec<Feature>,
+    /// Whether the features still need to be restored. This is shared with the panic hook.
+    active: Arc<AtomicBool>,
+    has_panic_hook: bool,
+}
+
+impl<T: Terminal> Session<T> {
+    fn enable(&mut self, feature: Feature) -> io::Result<()> {
+        write_setup(&feature, &mut self.terminal)?;
+        self.features.push(feature);
+        Ok(())
+    }
+
+    /// Returns a panic hook which disables the session's features unless the session has
+    /// already been restored.
+    fn panic_teardown(&self) -> impl Fn(&mut PlatformHandle) + Send + Sync + 'static {
+        let mut teardown = Vec::new();
+        // Writing to a `Vec` can't fail.
+        let _ = write_teardown(&self.features, &mut teardown);
+        let active = self.active.clone();
+        move |handle| {
+            if active.swap(false, Ordering::SeqCst) {
+                let _ = handle.write_all(&teardown);
+                let _ = handle.flush();
+            }
+        }
+    }
+
+    /// Disables the session's features in reverse order and leaves raw mode.
+    ///
+    /// Calling this more than once has no further effect once the features have been disabled.
+    /// If writing to the terminal fails then the error is returned and the session stays active
+    /// so that restoring can be tried again.
+    pub fn restore(&mut self) -> io::Result<()> {
+        if !self.is_active() {
+            return Ok(());
+        }
+        write_teardown(&self.features, &mut self.terminal)?;
+        self.terminal.flush()?;
+        self.active.store(false, Ordering::SeqCst);
+        if self.features.contains(&Feature::RawMode) {
+            self.terminal.enter_cooked_mode()?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` until the session has been restored.
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// The Kitty keyboard flags pushed by this session, if any.
+    pub fn kitty_keyboard_flags(&self) -> Option<KittyKeyboardFlags> {
+        self.features.iter().find_map(|feature| match feature {
+            Feature::KittyKeyboard(flags) => Some(*flags),
+            _ => None,
+        })
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.terminal
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.terminal
+    }
+}
+
+impl<T: Terminal> Drop for Session<T> {
+    fn drop(&mut self) {
+        // The panic hook has already restored the terminal.
+        if !self.has_panic_hook || !std::thread::panicking() {
+            let _ = self.restore();
+        }
+    }
+}
+
+impl<T: Terminal> io::Write for Session<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.terminal.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.terminal.flush()
+    }
+}
+
+impl<T: Terminal> Terminal for Session<T> {
+    fn enter_raw_mode(&mut self) -> io::Result<()> {
+        self.terminal.enter_raw_mode()
+    }
+
+    fn enter_cooked_mode(&mut self) -> io::Result<()> {
+        self.terminal.enter_cooked_mode()
+    }
+
+    fn get_dimensions(&self) -> io::Result<WindowSize> {
+        self.terminal.get_dimensions()
+    }
+
+    fn event_reader(&self) -> EventReader {
+        self.terminal.event_reader()
+    }
+
+    fn poll<F: Fn(&Event) -> bool>(
+        &self,
+        filter: F,
+        timeout: Option<Duration>,
+    ) -> io::Result<bool> {
+        self.terminal.poll(filter, timeout)
+    }
+
+    fn read<F: Fn(&Event) -> bool>(&self, filter: F) -> io::Result<Event> {
+        self.terminal.read(filter)
+    }
+
+    /// Disables the session's features while the process is stopped and enables them again
+    /// once the process is continued.
+    fn suspend(&mut self) -> io::Result<()> {
+        if !self.is_active() {
+            return self.terminal.suspend();
+        }
+        write_teardown(&self.features, &mut self.terminal)?;
+        self.terminal.flush()?;
+        let result = self.terminal.suspend();
+        for feature in &self.features {
+            write_setup(feature, &mut self.terminal)?;
+        }
+        self.terminal.flush()?;
+        result
+    }
+
+    /// Sets a panic hook which disables the session's features before calling `f`, so that
+    /// anything `f` writes ends up on the main screen.
+    fn set_panic_hook(&mut self, f: impl Fn(&mut PlatformHandle) + Send + Sync + 'static) {
+        let teardown = self.panic_teardown();
+        self.terminal.set_panic_hook(move |handle| {
+            teardown(handle);
+            f(handle);
+        });
+        self.has_panic_hook = true;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use crate::VirtualTerminal;
+
+    use super::*;
+
+    /// A terminal which can be inspected after the session owning it has been dropped and whose
+    /// writes can be made to fail.
+    #[derive(Debug, Clone)]
+    struct Shared {
+        terminal: Arc<Mutex<VirtualTerminal>>,
+        fail_writes: Arc<AtomicBool>,
+    }
+
+    impl Shared {
+        fn new() -> Self {
+            let terminal = VirtualTerminal::new(WindowSize::TEST);
+            Self {
+                terminal: Arc::new(Mutex::new(terminal)),
+                fail_writes: Arc::new(AtomicBool::new(false)),
+            }
+        }
+
+        fn take_output(&self) -> String {
+            String::from_utf8(self.terminal.lock().unwrap().take_output()).unwrap()
+        }
+
+        fn is_raw_mode(&self) -> bool {
+            self.terminal.lock().unwrap().is_raw_mode()
+        }
+    }
+
+    impl io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.fail_writes.load(Ordering::SeqCst) {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.terminal.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.terminal.lock().unwrap().flush()
+        }
+    }
+
+    impl Terminal for Shared {
+        fn enter_raw_mode(&mut self) -> io::Result<()> {
+            self.terminal.lock().unwrap().enter_raw_mode()
+        }
+
+        fn enter_cooked_mode(&mut self) -> io::Result<()> {
+            self.terminal.lock().unwrap().enter_cooked_mode()
+        }
+
+        fn get_dimensions(&self) -> io::Result<WindowSize> {
+            self.terminal.lock().unwrap().get_dimensions()
+        }
+
+        fn event_reader(&self) -> EventReader {
+            self.terminal.lock().unwrap().event_reader()
+        }
+
+        fn poll<F: Fn(&Event) -> bool>(
+            &self,
+            filter: F,
+            timeout: Option<Duration>,
+        ) -> io::Result<bool> {
+            self.event_reader().poll(timeout, filter)
+        }
+
+        fn read<F: Fn(&Event) -> bool>(&self, filter: F) -> io::Result<Event> {
+            self.event_reader().read(filter)
+        }
+
+        fn set_panic_hook(&mut self, f: impl Fn(&mut PlatformHandle) + Send + Sync + 'static) {
+            self.terminal.lock().unwrap().set_panic_hook(f)
+        }
+    }
+
+    fn session(terminal: &Shared) -> Session<Shared> {
+        let session = SessionBuilder::new()
+            .alternate_screen(true)
+            .bracketed_paste(true)
+            .build(terminal.clone())
+            .unwrap();
+        assert_eq!(terminal.take_output(), "\x1b[?1049h\x1b[?2004h");
+        assert!(terminal.is_raw_mode());
+        session
+    }
+
+    #[test]
+    fn restore_on_drop() {
+        let terminal = Shared::new();
+        drop(session(&terminal));
+        assert_eq!(terminal.take_output(), "\x1b[?2004l\x1b[?1049l");
+        assert!(!terminal.is_raw_mode());
+    }
+
+    #[test]
+    fn restore_once() {
+        let terminal = Shared::new();
+        let mut session = session(&terminal);
+        session.restore().unwrap();
+        assert!(!session.is_active());
+        assert_eq!(terminal.take_output(), "\x1b[?2004l\x1b[?1049l");
+        assert!(!terminal.is_raw_mode());
+
+        // Neither restoring again nor dropping the session writes anything.
+        session.restore().unwrap();
+        drop(session);
+        assert_eq!(terminal.take_output(), "");
+    }
+
+    #[test]
+    fn restore_after_failure() {
+        let terminal = Shared::new();
+        let mut session = session(&terminal);
+        terminal.fail_writes.store(true, Ordering::SeqCst);
+        assert!(session.restore().is_err());
+        assert!(session.is_active());
+        assert!(terminal.is_raw_mode());
+
+        terminal.fail_writes.store(false, Ordering::SeqCst);
+        session.restore().unwrap();
+        assert!(!session.is_active());
+        assert_eq!(terminal.take_output(), "\x1b[?2004l\x1b[?1049l");
+        assert!(!terminal.is_raw_mode());
+    }
+
+    #[test]
+    fn teardown_order() {
+        let features = [
+            Feature::RawMode,
+            Feature::AlternateScreen,
+            Feature::Title("title".to_string()),
+            Feature::BracketedPaste,
+            Feature::KittyKeyboard(KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES),
+        ];
+        let mut teardown = Vec::new();
+        write_teardown(&features, &mut teardown).unwrap();
+        // Kitty flags are popped before leaving the alternate screen, and the title is popped
+        // before leaving the alternate screen as well.
+        assert_eq!(
+            String::from_utf8(teardown).unwrap(),
+            "\x1b[<1u\x1b[?2004l\x1b[23;0t\x1b[?1049l"
+        );
+    }
+}
@@ -0,0 +1,341 @@
+// Synchronized output lets an application tell the terminal to hold off on rendering while it
+// writes a frame so that the user never sees a half-drawn screen ("tearing").
+//
+// There are two encodings. The DCS form is the original proposal from iTerm2:
+// <https://gitlab.com/gnachman/iterm2/-/wikis/synchronized-updates-spec>
+// and the DEC private mode (2026) form is the one most terminals implement today:
+// <https://gist.github.com/christianparpart/d8a62cc1ab659194337d73e399004036>
+// Terminals end a synchronized update on their own after a timeout but the timeout varies widely
+// (from 150ms to indefinitely) so `SynchronizedWriter` keeps its own.
+
+use std::{
+    fmt::{self, Display},
+    io::{self, Write as _},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    escape::{
+        csi::{self, Csi},
+        dcs::Dcs,
+    },
+    Event, EventReader, WindowSize,
+};
+
+use super::{PlatformHandle, Query, QueryReply, Terminal};
+
+const SYNCHRONIZED_OUTPUT: csi::DecPrivateMode =
+    csi::DecPrivateMode::Code(csi::DecPrivateModeCode::SynchronizedOutput);
+
+/// The encoding used to begin and end a synchronized update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SynchronizedUpdate {
+    /// Set and reset `csi::DecPrivateModeCode::SynchronizedOutput` (mode 2026).
+    DecPrivateMode,
+    /// The legacy `Dcs::BeginSynchronizedUpdate` and `Dcs::EndSynchronizedUpdate` sequences.
+    Dcs,
+}
+
+impl SynchronizedUpdate {
+    /// The escape sequence which begins a synchronized update.
+    pub fn begin(self) -> impl Display {
+        Sequence {
+            method: self,
+            begin: true,
+        }
+    }
+
+    /// The escape sequence which ends a synchronized update.
+    ///
+    /// Writing this sequence when no synchronized update is active is harmless.
+    pub fn end(self) -> impl Display {
+        Sequence {
+            method: self,
+            begin: false,
+        }
+    }
+
+    /// Queries the terminal for support of mode 2026.
+    ///
+    /// If the terminal reports that it recognizes the mode then `DecPrivateMode` is returned.
+    /// Otherwise the `Dcs` form is returned: terminals which don't support it ignore it.
+    ///
+    /// Events which are not replies to this query are left in the terminal's event reader. The
+    /// terminal should be in raw mode while querying.
+    pub fn detect<T: Terminal>(terminal: &mut T, timeout: Duration) -> io::Result<Self> {
+        let replies = terminal.query(&[Query::DecPrivateMode(SYNCHRONIZED_OUTPUT)], timeout)?;
+        let method = match replies[0] {
+            QueryReply::DecPrivateMode(csi::DecModeSetting::Set | csi::DecModeSetting::Reset) => {
+                Self::DecPrivateMode
+            }
+            _ => Self::Dcs,
+        };
+        Ok(method)
+    }
+}
+
+struct Sequence {
+    method: SynchronizedUpdate,
+    begin: bool,
+}
+
+impl Display for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.method, self.begin) {
+            (SynchronizedUpdate::DecPrivateMode, true) => {
+                Csi::Mode(csi::Mode::SetDecPrivateMode(SYNCHRONIZED_OUTPUT)).fmt(f)
+            }
+            (SynchronizedUpdate::DecPrivateMode, false) => {
+                Csi::Mode(csi::Mode::ResetDecPrivateMode(SYNCHRONIZED_OUTPUT)).fmt(f)
+            }
+            (SynchronizedUpdate::Dcs, true) => Dcs::BeginSynchronizedUpdate.fmt(f),
+            (SynchronizedUpdate::Dcs, false) => Dcs::EndSynchronizedUpdate.fmt(f),
+        }
+    }
+}
+
+/// A writer which wraps each frame written to a terminal in a synchronized update.
+///
+/// Call [Self::begin_frame] before drawing and [Self::end_frame] once the frame is complete.
+/// Everything written in between is shown by the terminal at once.
+///
+/// The update is ended automatically:
+///
+/// * when a frame has been open for longer than the frame timeout. The timeout is only checked
+///   on the next write or flush or by [Self::check_frame_timeout], so an application which may
+///   stop writing in the middle of a frame should call that from its event loop. See
+///   [Self::set_frame_timeout].
+/// * when [Self::begin_frame] is called while a frame is already open.
+/// * when this writer is dropped, including while unwinding from a panic.
+/// * in the panic hook set by [Terminal::set_panic_hook] on this writer.
+///
+/// This type implements [Terminal] itself so it can be used in place of the wrapped terminal.
+#[derive(Debug)]
+pub struct SynchronizedWriter<T: Terminal> {
+    /// This is only `None` after `into_inner`.
+    terminal: Option<T>,
+    method: SynchronizedUpdate,
+    frame_timeout: Duration,
+    frame_start: Option<Instant>,
+}
+
+impl<T: Terminal> SynchronizedWriter<T> {
+    /// The default for [Self::set_frame_timeout].
+    pub const DEFAULT_FRAME_TIMEOUT: Duration = Duration::from_secs(1);
+
+    pub fn new(terminal: T, method: SynchronizedUpdate) -> Self {
+        Self {
+            terminal: Some(terminal),
+            method,
+            frame_timeout: Self::DEFAULT_FRAME_TIMEOUT,
+            frame_start: None,
+        }
+    }
+
+    /// Sets the longest time a frame may stay open before the update is ended automatically.
+    pub fn set_frame_timeout(&mut self, timeout: Duration) {
+        self.frame_timeout = timeout;
+    }
+
+    pub fn method(&self) -> SynchronizedUpdate {
+        self.method
+    }
+
+    /// Returns `true` if a frame has begun and has not yet ended.
+    pub fn is_in_frame(&self) -> bool {
+        self.frame_start.is_some()
+    }
+
+    /// Begins a synchronized update.
+    ///
+    /// If a frame is already open it is ended first.
+    pub fn begin_frame(&mut self) -> io::Result<()> {
+        if self.frame_start.is_some() {
+            self.end_frame()?;
+        }
+        let begin = self.method.begin();
+        write!(self.get_mut(), "{begin}")?;
+        self.frame_start = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Ends the synchronized update and flushes the terminal.
+    ///
+    /// This does nothing if no frame is open.
+    pub fn end_frame(&mut self) -> io::Result<()> {
+        if self.frame_start.take().is_none() {
+            return Ok(());
+        }
+        let end = self.method.end();
+        let terminal = self.get_mut();
+        write!(terminal, "{end}")?;
+        terminal.flush()
+    }
+
+    pub fn get_ref(&self) -> &T {
+        self.terminal
+            .as_ref()
+            .expect("terminal is only taken by into_inner")
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.terminal
+            .as_mut()
+            .expect("terminal is only taken by into_inner")
+    }
+
+    /// Ends any open frame and returns the wrapped terminal.
+    pub fn into_inner(mut self) -> io::Result<T> {
+        self.end_frame()?;
+        Ok(self
+            .terminal
+            .take()
+            .expect("terminal is only taken by into_inner"))
+    }
+
+    /// When the open frame times out, or `None` if no frame is open.
+    ///
+    /// This is useful for bounding the timeout passed to `Terminal::poll` so that the event
+    /// loop can call [Self::check_frame_timeout] in time.
+    pub fn frame_deadline(&self) -> Option<Instant> {
+        self.frame_start.map(|start| start + self.frame_timeout)
+    }
+
+    /// Ends the open frame if it has been open for longer than the frame timeout.
+    ///
+    /// Writes and flushes check the timeout themselves.
+    pub fn check_frame_timeout(&mut self) -> io::Result<()> {
+        if self
+            .frame_start
+            .is_some_and(|start| start.elapsed() >= self.frame_timeout)
+        {
+            self.end_frame()?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Terminal> Drop for SynchronizedWriter<T> {
+    fn drop(&mut self) {
+        if self.terminal.is_some() {
+            let _ = self.end_frame();
+        }
+    }
+}
+
+impl<T: Terminal> io::Write for SynchronizedWriter<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check_frame_timeout()?;
+        self.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.check_frame_timeout()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<T: Terminal> Terminal for SynchronizedWriter<T> {
+    fn enter_raw_mode(&mut self) -> io::Result<()> {
+        self.get_mut().enter_raw_mode()
+    }
+
+    fn enter_cooked_mode(&mut self) -> io::Result<()> {
+        self.get_mut().enter_cooked_mode()
+    }
+
+    fn get_dimensions(&self) -> io::Result<WindowSize> {
+        self.get_ref().get_dimensions()
+    }
+
+    fn event_reader(&self) -> EventReader {
+        self.get_ref().event_reader()
+    }
+
+    fn poll<F: Fn(&Event) -> bool>(
+        &self,
+        filter: F,
+        timeout: Option<Duration>,
+    ) -> io::Result<bool> {
+        self.get_ref().poll(filter, timeout)
+    }
+
+    fn read<F: Fn(&Event) -> bool>(&self, filter: F) -> io::Result<Event> {
+        self.get_ref().read(filter)
+    }
+
+    fn suspend(&mut self) -> io::Result<()> {
+        self.end_frame()?;
+        self.get_mut().suspend()
+    }
+
+    fn set_panic_hook(&mut self, f: impl Fn(&mut PlatformHandle) + Send + Sync + 'static) {
+        let end = self.method.end().to_string();
+        self.get_mut().set_panic_hook(move |handle| {
+            // End any synchronized update first so that the terminal renders whatever the hook
+            // writes.
+            let _ = handle.write_all(end.as_bytes());
+            f(handle);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::VirtualTerminal;
+
+    use super::*;
+
+    fn writer(method: SynchronizedUpdate) -> SynchronizedWriter<VirtualTerminal> {
+        let terminal = VirtualTerminal::new(WindowSize::TEST);
+        SynchronizedWriter::new(terminal, method)
+    }
+
+    fn take_output(writer: &mut SynchronizedWriter<VirtualTerminal>) -> String {
+        String::from_utf8(writer.get_mut().take_output()).unwrap()
+    }
+
+    #[test]
+    fn framing() {
+        let mut writer = writer(SynchronizedUpdate::DecPrivateMode);
+        writer.begin_frame().unwrap();
+        write!(writer, "a").unwrap();
+        assert!(writer.is_in_frame());
+        writer.end_frame().unwrap();
+        assert!(!writer.is_in_frame());
+        // Ending a frame which isn't open writes nothing.
+        writer.end_frame().unwrap();
+        assert_eq!(take_output(&mut writer), "\x1b[?2026ha\x1b[?2026l");
+
+        // Beginning a frame while one is open ends the open one first.
+        let mut writer = self::writer(SynchronizedUpdate::Dcs);
+        writer.begin_frame().unwrap();
+        write!(writer, "a").unwrap();
+        writer.begin_frame().unwrap();
+        write!(writer, "b").unwrap();
+        let terminal = writer.into_inner().unwrap();
+        assert_eq!(
+            std::str::from_utf8(terminal.output()).unwrap(),
+            "\x1bP=1s\x1b\\a\x1bP=2s\x1b\\\x1bP=1s\x1b\\b\x1bP=2s\x1b\\"
+        );
+    }
+
+    #[test]
+    fn frame_timeout() {
+        let mut writer = writer(SynchronizedUpdate::DecPrivateMode);
+        writer.set_frame_timeout(Duration::ZERO);
+        assert_eq!(writer.frame_deadline(), None);
+        writer.begin_frame().unwrap();
+        assert!(writer.frame_deadline().is_some());
+
+        // The frame is ended without writing anything else.
+        writer.check_frame_timeout().unwrap();
+        assert!(!writer.is_in_frame());
+        assert_eq!(take_output(&mut writer), "\x1b[?2026h\x1b[?2026l");
+
+        // Or before the next write.
+        writer.begin_frame().unwrap();
+        write!(writer, "a").unwrap();
+        assert_eq!(take_output(&mut writer), "\x1b[?2026h\x1b[?2026la");
+    }
+}
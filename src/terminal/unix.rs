@@ -2,12 +2,28 @@ use rustix::termios::{self, Termios};
 use std::{
     fs,
     io::{self, BufWriter, IsTerminal as _, Write as _},
-    os::unix::prelude::*,
+    os::unix::{net::UnixStream, prelude::*},
+    path::Path,
+    sync::Arc,
 };
 
-use crate::{event::source::UnixEventSource, Event, EventReader, WindowSize};
+use parking_lot::Mutex;
 
-use super::Terminal;
+use crate::{
+    event::{
+        source::{self, SignalWatcher, UnixEventSource},
+        Signal,
+    },
+    Event, EventReader, WindowSize,
+};
+
+use super::{Terminal, TerminalMode};
+
+#[cfg(any(feature = "tokio", feature = "async-io"))]
+mod async_output;
+
+#[cfg(any(feature = "tokio", feature = "async-io"))]
+pub use async_output::AsyncOutput;
 
 const BUF_SIZE: usize = 4096;
 
@@ -81,10 +97,11 @@ fn open_pty() -> io::Result<(FileDescriptor, FileDescriptor)> {
 }
 
 fn open_dev_tty() -> io::Result<FileDescriptor> {
-    let file = fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open("/dev/tty")?;
+    open_tty("/dev/tty")
+}
+
+fn open_tty(path: impl AsRef<Path>) -> io::Result<FileDescriptor> {
+    let file = fs::OpenOptions::new().read(true).write(true).open(path)?;
     Ok(FileDescriptor::Owned(file.into()))
 }
 
@@ -93,8 +110,9 @@ impl From<termios::Winsize> for WindowSize {
         Self {
             cols: size.ws_col,
             rows: size.ws_row,
-            pixel_width: Some(size.ws_xpixel),
-            pixel_height: Some(size.ws_ypixel),
+            // Many terminals, and SSH, leave the pixel size zeroed.
+            pixel_width: (size.ws_xpixel != 0).then_some(size.ws_xpixel),
+            pixel_height: (size.ws_ypixel != 0).then_some(size.ws_ypixel),
         }
     }
 }
@@ -114,46 +132,172 @@ pub struct UnixTerminal {
     reader: EventReader,
     /// Buffered handle to the writer (stdout or `/dev/tty`)
     write: BufWriter<FileDescriptor>,
+    /// Another handle to the writer for the panic hook.
+    panic_write: Arc<FileDescriptor>,
     /// The termios of the PTY's writer detected during `Self::new`.
     original_termios: Termios,
+    /// The termios set by `Self::enter_raw_mode` or `Self::set_mode`, or `None` in cooked mode.
+    /// This is shared with the event source so that it can re-apply the mode when the process is
+    /// continued.
+    raw_termios: Arc<Mutex<Option<Termios>>>,
+    signals: Arc<SignalWatcher>,
+    /// See `Self::notify_resized`.
+    resize_pipe: Arc<UnixStream>,
     has_panic_hook: bool,
 }
 
 impl UnixTerminal {
+    /// Creates a terminal for the process's controlling terminal.
+    ///
+    /// The terminal reads from stdin and writes to stdout when they are terminals and otherwise
+    /// uses `/dev/tty`.
     pub fn new() -> io::Result<Self> {
         let (read, write) = open_pty()?;
-        let source = UnixEventSource::new(read, write.try_clone()?)?;
+        Self::with_fds(read, write)
+    }
+
+    /// Creates a terminal which reads from `read` and writes to `write`.
+    ///
+    /// Both descriptors should refer to the same terminal device, for example duplicates of the
+    /// subsidiary side of a PTY. The dimensions are read from `write`.
+    ///
+    /// `Event::WindowResized` is emitted when the process receives SIGWINCH and the size of this
+    /// terminal changed. Note that the kernel only sends SIGWINCH to the foreground process group
+    /// of the resized terminal, so a process which doesn't have the terminal as its controlling
+    /// terminal, for example a server which owns several PTYs, receives no resize events for it.
+    /// Such a process should call [Self::notify_resized] after resizing the terminal instead.
+    pub fn from_fds(read: OwnedFd, write: OwnedFd) -> io::Result<Self> {
+        Self::with_fds(FileDescriptor::Owned(read), FileDescriptor::Owned(write))
+    }
+
+    /// Opens the terminal device at `path`, for example `/dev/pts/7`, for reading and writing.
+    ///
+    /// See [Self::from_fds] for how resizes are reported.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let read = open_tty(path)?;
+        let write = read.try_clone()?;
+        Self::with_fds(read, write)
+    }
+
+    fn with_fds(read: FileDescriptor, write: FileDescriptor) -> io::Result<Self> {
+        let raw_termios = Arc::new(Mutex::new(None));
+        let source = UnixEventSource::new(read, write.try_clone()?, raw_termios.clone())?;
         let original_termios = termios::tcgetattr(&write)?;
+        let signals = source.signal_watcher();
+        let resize_pipe = source.resize_pipe();
         let reader = EventReader::new(source);
 
         Ok(Self {
             reader,
+            panic_write: Arc::new(write.try_clone()?),
             write: BufWriter::with_capacity(BUF_SIZE, write),
             original_termios,
+            raw_termios,
+            signals,
+            resize_pipe,
             has_panic_hook: false,
         })
     }
-}
 
-impl Terminal for UnixTerminal {
-    fn enter_raw_mode(&mut self) -> io::Result<()> {
-        let mut termios = termios::tcgetattr(self.write.get_ref())?;
-        termios.make_raw();
+    /// Delivers the given signals as `Event::Signal` events.
+    ///
+    /// This allows applications to save their state and restore the terminal from their event
+    /// loop rather than from a signal handler. Note that watching a signal replaces its default
+    /// action, which is to terminate the process for all of these signals, so the application
+    /// is responsible for exiting when it reads the event.
+    ///
+    /// Watching a signal which is already watched has no effect.
+    pub fn watch_signals(&self, signals: &[Signal]) -> io::Result<()> {
+        for signal in signals {
+            self.signals.watch(*signal)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the terminal's size as if the process had received SIGWINCH, emitting an
+    /// `Event::WindowResized` if the size changed.
+    ///
+    /// This is for terminals which are resized without the process receiving SIGWINCH, see
+    /// [Self::from_fds]. For example a server calls this after applying a window size change
+    /// from a network client to the PTY. This only wakes the event reader so it can be called
+    /// while another thread is reading events.
+    pub fn notify_resized(&self) -> io::Result<()> {
+        match (&*self.resize_pipe).write(&[0]) {
+            Ok(_) => Ok(()),
+            // The pipe is full: the size will be checked for the earlier notifications.
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Sets the terminal's mode. This is a more flexible version of `Terminal::enter_raw_mode`.
+    ///
+    /// The mode is applied to the termios which the terminal had when it was created, so flags
+    /// not controlled by [TerminalMode] keep their original values. `Terminal::enter_cooked_mode`
+    /// restores the original termios.
+    pub fn set_mode(&mut self, mode: TerminalMode) -> io::Result<()> {
+        let mut termios = self.original_termios.clone();
+        mode.apply(&mut termios);
         termios::tcsetattr(
             self.write.get_ref(),
             termios::OptionalActions::Flush,
             &termios,
         )?;
+        *self.raw_termios.lock() = Some(termios);
 
         Ok(())
     }
 
+    /// Reads the terminal's current mode.
+    pub fn mode(&self) -> io::Result<TerminalMode> {
+        let termios = termios::tcgetattr(self.write.get_ref())?;
+        Ok(TerminalMode::from_termios(&termios))
+    }
+
+    /// The file descriptor which the terminal writes to (stdout or `/dev/tty`).
+    ///
+    /// Flush the terminal before writing to the descriptor directly so that buffered output
+    /// isn't reordered. See `UnixTerminal::async_output` for writing from async code.
+    pub fn output_fd(&self) -> BorrowedFd<'_> {
+        self.write.get_ref().as_fd()
+    }
+
+    /// Creates a handle for writing to the terminal from async code with the async-io reactor,
+    /// see [AsyncOutput].
+    ///
+    /// Buffered output is flushed first so that it isn't reordered with writes through the
+    /// handle. Fails if the terminal's output isn't a terminal device.
+    #[cfg(feature = "async-io")]
+    pub fn async_output(&mut self) -> io::Result<AsyncOutput> {
+        self.write.flush()?;
+        AsyncOutput::async_io(self.write.get_ref())
+    }
+
+    /// Creates a handle for writing to the terminal from async code with the tokio reactor, see
+    /// [AsyncOutput].
+    ///
+    /// This must be called within a tokio runtime which has IO enabled. Buffered output is
+    /// flushed first so that it isn't reordered with writes through the handle. Fails if the
+    /// terminal's output isn't a terminal device.
+    #[cfg(feature = "tokio")]
+    pub fn tokio_output(&mut self) -> io::Result<AsyncOutput> {
+        self.write.flush()?;
+        AsyncOutput::tokio(self.write.get_ref())
+    }
+}
+
+impl Terminal for UnixTerminal {
+    fn enter_raw_mode(&mut self) -> io::Result<()> {
+        self.set_mode(TerminalMode::raw())
+    }
+
     fn enter_cooked_mode(&mut self) -> io::Result<()> {
         termios::tcsetattr(
             self.write.get_ref(),
             termios::OptionalActions::Now,
             &self.original_termios,
         )?;
+        *self.raw_termios.lock() = None;
         Ok(())
     }
 
@@ -203,11 +347,39 @@ impl Terminal for UnixTerminal {
         self.reader.read(filter)
     }
 
+    fn suspend(&mut self) -> io::Result<()> {
+        self.flush()?;
+        let raw_termios = self.raw_termios.lock().clone();
+        if raw_termios.is_some() {
+            termios::tcsetattr(
+                self.write.get_ref(),
+                termios::OptionalActions::Now,
+                &self.original_termios,
+            )?;
+        }
+        // SIGTSTP's default action stops the process. Execution continues here once the process
+        // receives SIGCONT.
+        signal_hook::low_level::raise(signal_hook::consts::SIGTSTP)?;
+        // If the process was continued in the background then the event source re-applies raw
+        // mode once the process is brought to the foreground. See `UnixEventSource::try_read`.
+        if let Some(raw_termios) = raw_termios {
+            if source::is_foreground(self.write.get_ref())? {
+                termios::tcsetattr(
+                    self.write.get_ref(),
+                    termios::OptionalActions::Now,
+                    &raw_termios,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
     fn set_panic_hook(&mut self, f: impl Fn(&mut FileDescriptor) + Send + Sync + 'static) {
         let original_termios = self.original_termios.clone();
+        let panic_write = self.panic_write.clone();
         let hook = std::panic::take_hook();
         std::panic::set_hook(Box::new(move |info| {
-            if let Ok((_read, mut write)) = open_pty() {
+            if let Ok(mut write) = panic_write.try_clone() {
                 f(&mut write);
                 let _ = termios::tcsetattr(write, termios::OptionalActions::Now, &original_termios);
             }
@@ -235,3 +407,185 @@ impl io::Write for UnixTerminal {
         self.write.flush()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::Read as _,
+        os::unix::net::UnixStream,
+        time::{Duration, Instant},
+    };
+
+    use crate::{event::Token, pty::PtyMaster};
+
+    use super::*;
+
+    /// Signals are delivered to every terminal in the process so tests which raise signals or
+    /// read events which a signal could interleave with run one at a time.
+    static SIGNALS: Mutex<()> = parking_lot::const_mutex(());
+
+    fn open_pty() -> (PtyMaster, UnixTerminal) {
+        let (master, subsidiary) = crate::pty::open(WindowSize::TEST).unwrap();
+        let terminal = UnixTerminal::from_fds(subsidiary.try_clone().unwrap(), subsidiary).unwrap();
+        (master, terminal)
+    }
+
+    #[test]
+    fn user_fds_timers_and_events() {
+        let _signals = SIGNALS.lock();
+        let (_master, terminal) = open_pty();
+        let reader = terminal.event_reader();
+
+        let (mut socket, mut peer) = UnixStream::pair().unwrap();
+        reader
+            .register_fd(socket.as_fd().try_clone_to_owned().unwrap(), Token(1))
+            .unwrap();
+        reader.add_timer(Instant::now() + Duration::from_millis(50), Token(2));
+        reader.add_timer(Instant::now() + Duration::from_secs(60), Token(3));
+        assert!(reader.cancel_timer(Token(3)));
+
+        peer.write_all(b"hello").unwrap();
+        // The socket is reported once while the event waits in the queue, even though it stays
+        // readable.
+        let is_timer = |event: &Event| matches!(event, Event::Timer(_));
+        assert_eq!(terminal.read(is_timer).unwrap(), Event::Timer(Token(2)));
+        assert_eq!(terminal.read(|_| true).unwrap(), Event::Readable(Token(1)));
+        let mut buf = [0; 5];
+        socket.read_exact(&mut buf).unwrap();
+        assert!(!terminal
+            .poll(|_| true, Some(Duration::from_millis(10)))
+            .unwrap());
+
+        assert!(reader.deregister_fd(Token(1)).is_some());
+
+        // Unlike a wake, a user event makes `poll` return `true`.
+        let sender = reader.user_event_sender();
+        std::thread::spawn(move || sender.send(42u32));
+        let is_user = |event: &Event| matches!(event, Event::User(_));
+        assert!(terminal.poll(is_user, None).unwrap());
+        let Event::User(event) = terminal.read(is_user).unwrap() else {
+            unreachable!()
+        };
+        assert_eq!(event.downcast_ref::<u32>(), Some(&42));
+    }
+
+    #[test]
+    fn resume() {
+        let _signals = SIGNALS.lock();
+        let (_master, mut terminal) = open_pty();
+        terminal.enter_raw_mode().unwrap();
+
+        // The PTY isn't the controlling terminal of the test process so the raw mode isn't
+        // re-applied, but the resume is still reported.
+        signal_hook::low_level::raise(signal_hook::consts::SIGCONT).unwrap();
+        assert_eq!(terminal.read(|_| true).unwrap(), Event::Resumed);
+        assert_eq!(terminal.mode().unwrap(), TerminalMode::raw());
+    }
+
+    #[test]
+    fn watched_signals() {
+        let _signals = SIGNALS.lock();
+        let (_master, terminal) = open_pty();
+        terminal
+            .watch_signals(&[Signal::User1, Signal::User2])
+            .unwrap();
+
+        signal_hook::low_level::raise(signal_hook::consts::SIGUSR2).unwrap();
+        assert_eq!(
+            terminal.read(|_| true).unwrap(),
+            Event::Signal(Signal::User2)
+        );
+        signal_hook::low_level::raise(signal_hook::consts::SIGUSR1).unwrap();
+        assert_eq!(
+            terminal.read(|_| true).unwrap(),
+            Event::Signal(Signal::User1)
+        );
+        assert!(!terminal.poll(|_| true, Some(Duration::ZERO)).unwrap());
+    }
+
+    #[test]
+    fn closed() {
+        let _signals = SIGNALS.lock();
+        let (mut master, mut terminal) = open_pty();
+        terminal.enter_raw_mode().unwrap();
+
+        // Input written before the hangup is still read.
+        master.write_all(b"q").unwrap();
+        assert!(matches!(terminal.read(|_| true).unwrap(), Event::Key(_)));
+        drop(master);
+        assert_eq!(terminal.read(|_| true).unwrap(), Event::Closed);
+        assert_eq!(
+            terminal.poll(|_| true, None).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn resize() {
+        let _signals = SIGNALS.lock();
+        let (master, terminal) = open_pty();
+        assert_eq!(terminal.get_dimensions().unwrap(), WindowSize::TEST);
+
+        let resized = WindowSize {
+            cols: 100,
+            rows: 30,
+            pixel_width: Some(1000),
+            pixel_height: Some(600),
+        };
+        master.resize(resized).unwrap();
+        assert_eq!(terminal.get_dimensions().unwrap(), resized);
+
+        // The test process isn't sent SIGWINCH for the PTY.
+        assert!(!terminal.poll(|_| true, Some(Duration::ZERO)).unwrap());
+        terminal.notify_resized().unwrap();
+        assert_eq!(
+            terminal.read(|_| true).unwrap(),
+            Event::WindowResized(resized)
+        );
+        // The size didn't change since the last notification.
+        terminal.notify_resized().unwrap();
+        assert!(!terminal
+            .poll(|_| true, Some(Duration::from_millis(10)))
+            .unwrap());
+    }
+
+    #[test]
+    fn in_band_resize_after_sigwinch() {
+        let _signals = SIGNALS.lock();
+        let (mut master, mut terminal) = open_pty();
+        terminal.enter_raw_mode().unwrap();
+
+        // SIGWINCH reports a size without pixel dimensions, as over SSH.
+        let resized = WindowSize {
+            cols: 100,
+            rows: 30,
+            pixel_width: None,
+            pixel_height: None,
+        };
+        master.resize(resized).unwrap();
+        terminal.notify_resized().unwrap();
+        assert_eq!(
+            terminal.read(|_| true).unwrap(),
+            Event::WindowResized(resized)
+        );
+
+        // The in-band report of the same size includes the pixel dimensions and isn't emitted
+        // again.
+        master.write_all(b"\x1b[48;30;100;600;1000t").unwrap();
+        assert!(!terminal
+            .poll(|_| true, Some(Duration::from_millis(50)))
+            .unwrap());
+
+        // The pixel dimensions were recorded so a change of only those is still reported.
+        master.write_all(b"\x1b[48;30;100;1200;2000t").unwrap();
+        assert_eq!(
+            terminal.read(|_| true).unwrap(),
+            Event::WindowResized(WindowSize {
+                cols: 100,
+                rows: 30,
+                pixel_width: Some(2000),
+                pixel_height: Some(1200),
+            })
+        );
+    }
+}
@@ -0,0 +1,244 @@
+// Writing to the terminal from async code: the terminal device is opened again in non-blocking
+// mode and registered with the async runtime's reactor, so a write which the terminal can't take
+// yet yields to other tasks instead of blocking the executor's thread.
+
+use std::{
+    io,
+    os::fd::{AsFd, OwnedFd},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use rustix::fs::{Mode, OFlags};
+
+/// A handle for writing to a [UnixTerminal](super::UnixTerminal) without blocking.
+///
+/// This type is only available if the `tokio` or `async-io` feature is enabled. It implements
+/// tokio's `AsyncWrite` with the `tokio` feature and futures-io's `AsyncWrite` with the
+/// `async-io` feature. Create it with `UnixTerminal::async_output` for the async-io reactor or
+/// with `UnixTerminal::tokio_output` for the tokio reactor.
+///
+/// The handle is independent of the terminal's [EventReader](crate::EventReader) so it can be
+/// moved to another task than the one reading events.
+///
+/// The handle opens the terminal device again rather than duplicating the terminal's FD.
+/// `O_NONBLOCK` is a property of the open file, so setting it on the terminal's FD would also
+/// affect its stdin and the shell which the FD was inherited from, and a process which exits
+/// without restoring the flag would leave the shell with a non-blocking terminal.
+#[derive(Debug)]
+pub struct AsyncOutput {
+    fd: Registered,
+}
+
+#[derive(Debug)]
+enum Registered {
+    #[cfg(feature = "tokio")]
+    Tokio(tokio::io::unix::AsyncFd<OwnedFd>),
+    #[cfg(feature = "async-io")]
+    AsyncIo(async_io::Async<OwnedFd>),
+}
+
+impl AsyncOutput {
+    /// Registers the terminal device behind `fd` with the async-io reactor.
+    #[cfg(feature = "async-io")]
+    pub(super) fn async_io(fd: impl AsFd) -> io::Result<Self> {
+        let fd = async_io::Async::new_nonblocking(reopen(fd)?)?;
+        Ok(Self {
+            fd: Registered::AsyncIo(fd),
+        })
+    }
+
+    /// Registers the terminal device behind `fd` with the current tokio runtime's reactor.
+    #[cfg(feature = "tokio")]
+    pub(super) fn tokio(fd: impl AsFd) -> io::Result<Self> {
+        if tokio::runtime::Handle::try_current().is_err() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "AsyncOutput must be created within a tokio runtime",
+            ));
+        }
+        // Newer tokio deprecates this in favour of an `unsafe` constructor because it can't tell
+        // whether `T` owns the FD. An `OwnedFd` does, so it can't be closed while registered.
+        #[allow(deprecated)]
+        let fd =
+            tokio::io::unix::AsyncFd::with_interest(reopen(fd)?, tokio::io::Interest::WRITABLE)?;
+        Ok(Self {
+            fd: Registered::Tokio(fd),
+        })
+    }
+
+    fn poll_write_fd(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match &self.fd {
+            #[cfg(feature = "tokio")]
+            Registered::Tokio(fd) => loop {
+                let mut guard = ready!(fd.poll_write_ready(cx))?;
+                // `try_io` clears the readiness if the write would block.
+                if let Ok(result) = guard.try_io(|fd| write(fd.get_ref(), buf)) {
+                    return Poll::Ready(result);
+                }
+            },
+            #[cfg(feature = "async-io")]
+            Registered::AsyncIo(fd) => loop {
+                match write(fd.get_ref(), buf) {
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        ready!(fd.poll_writable(cx))?;
+                    }
+                    result => return Poll::Ready(result),
+                }
+            },
+        }
+    }
+}
+
+/// Opens the terminal device behind `fd` for writing in non-blocking mode.
+fn reopen(fd: impl AsFd) -> io::Result<OwnedFd> {
+    let path = rustix::termios::ttyname(fd, Vec::new())?;
+    let flags = OFlags::WRONLY | OFlags::NONBLOCK | OFlags::NOCTTY | OFlags::CLOEXEC;
+    let fd = rustix::fs::open(path.as_c_str(), flags, Mode::empty())?;
+    Ok(fd)
+}
+
+fn write(fd: &OwnedFd, buf: &[u8]) -> io::Result<usize> {
+    loop {
+        match rustix::io::write(fd, buf) {
+            Err(rustix::io::Errno::INTR) => continue,
+            result => return result.map_err(Into::into),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for AsyncOutput {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_fd(cx, buf)
+    }
+
+    /// Does nothing: writes aren't buffered.
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// Does nothing: the terminal stays open until the handle and the terminal are dropped.
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "async-io")]
+impl futures_io::AsyncWrite for AsyncOutput {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_fd(cx, buf)
+    }
+
+    /// Does nothing: writes aren't buffered.
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// Does nothing: the terminal stays open until the handle and the terminal are dropped.
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        future,
+        io::{Read as _, Write as _},
+        thread,
+    };
+
+    use crate::{terminal::UnixTerminal, Terminal as _, WindowSize};
+
+    use super::*;
+
+    type PollWrite = fn(Pin<&mut AsyncOutput>, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>;
+
+    /// Writes a frame which doesn't fit into the PTY's buffer through the handle, checking that
+    /// the write yields until the other end reads.
+    async fn write_pty(
+        new: fn(&mut UnixTerminal) -> io::Result<AsyncOutput>,
+        poll_write: PollWrite,
+    ) {
+        let size = WindowSize::TEST;
+        let (mut master, subsidiary) = crate::pty::open(size).unwrap();
+        let mut terminal =
+            UnixTerminal::from_fds(subsidiary.try_clone().unwrap(), subsidiary).unwrap();
+        terminal.enter_raw_mode().unwrap();
+        let is_nonblocking = |terminal: &UnixTerminal| {
+            rustix::fs::fcntl_getfl(terminal.output_fd())
+                .unwrap()
+                .contains(OFlags::NONBLOCK)
+        };
+
+        // Buffered output is written before the frame.
+        write!(terminal, "<").unwrap();
+        let mut output = new(&mut terminal).unwrap();
+        // The terminal's own FD stays blocking.
+        assert!(!is_nonblocking(&terminal));
+
+        let frame: Vec<u8> = (0..256 * 1024).map(|i| b'a' + (i % 26) as u8).collect();
+        let len = frame.len() + 1;
+        let reader = thread::spawn(move || {
+            let mut read = Vec::new();
+            let mut buf = [0; 4096];
+            while read.len() < len {
+                let n = master.read(&mut buf).unwrap();
+                read.extend_from_slice(&buf[..n]);
+            }
+            read
+        });
+
+        let mut written = 0;
+        let mut pending = 0;
+        future::poll_fn(|cx| {
+            while written < frame.len() {
+                match poll_write(Pin::new(&mut output), cx, &frame[written..]) {
+                    Poll::Ready(result) => written += result.unwrap(),
+                    Poll::Pending => {
+                        pending += 1;
+                        return Poll::Pending;
+                    }
+                }
+            }
+            Poll::Ready(())
+        })
+        .await;
+        assert!(pending > 0);
+
+        let read = reader.join().unwrap();
+        assert_eq!(read[0], b'<');
+        assert!(read[1..] == frame);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn tokio_write() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(write_pty(
+                UnixTerminal::tokio_output,
+                <AsyncOutput as tokio::io::AsyncWrite>::poll_write,
+            ));
+    }
+
+    #[cfg(feature = "async-io")]
+    #[test]
+    fn async_io_write() {
+        async_io::block_on(write_pty(
+            UnixTerminal::async_output,
+            <AsyncOutput as futures_io::AsyncWrite>::poll_write,
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+use std::{io, time::Duration};
+
+use crate::{
+    event::source::{VirtualEventSource, VirtualInput},
+    Event, EventReader, WindowSize,
+};
+
+use super::{PlatformHandle, Terminal};
+
+/// An in-memory terminal for testing applications without a real terminal.
+///
+/// Everything written to the terminal is captured and can be inspected with [Self::output].
+/// Input, resizes and other events are sent to the terminal's event reader with the
+/// [VirtualInput] handle from [Self::input]. The reader can also be passed to `EventStream::new`.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use std::io::Write as _;
+/// # use termina::{event::KeyCode, Event, Terminal as _, VirtualTerminal, WindowSize};
+/// let size = WindowSize { cols: 80, rows: 24, pixel_width: None, pixel_height: None };
+/// let mut terminal = VirtualTerminal::new(size);
+/// terminal.input().send_bytes(b"q");
+///
+/// let is_key = |event: &Event| matches!(event, Event::Key(_));
+/// assert!(terminal.poll(is_key, Some(Duration::ZERO))?);
+/// let Event::Key(key) = terminal.read(is_key)? else { unreachable!() };
+/// assert_eq!(key.code, KeyCode::Char('q'));
+///
+/// write!(terminal, "goodbye")?;
+/// assert_eq!(terminal.output(), b"goodbye");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct VirtualTerminal {
+    reader: EventReader,
+    input: VirtualInput,
+    output: Vec<u8>,
+    raw_mode: bool,
+}
+
+impl VirtualTerminal {
+    pub fn new(size: WindowSize) -> Self {
+        let input = VirtualInput::new(size);
+        let reader = EventReader::new(VirtualEventSource::new(input.clone()));
+        Self {
+            reader,
+            input,
+            output: Vec::new(),
+            raw_mode: false,
+        }
+    }
+
+    /// Returns a handle for sending input to this terminal.
+    pub fn input(&self) -> VirtualInput {
+        self.input.clone()
+    }
+
+    /// The bytes written to the terminal so far.
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Takes the bytes written to the terminal so far, clearing the output.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Whether the terminal is in raw mode. See `Terminal::enter_raw_mode`.
+    pub fn is_raw_mode(&self) -> bool {
+        self.raw_mode
+    }
+}
+
+impl Terminal for VirtualTerminal {
+    fn enter_raw_mode(&mut self) -> io::Result<()> {
+        self.raw_mode = true;
+        Ok(())
+    }
+
+    fn enter_cooked_mode(&mut self) -> io::Result<()> {
+        self.raw_mode = false;
+        Ok(())
+    }
+
+    fn get_dimensions(&self) -> io::Result<WindowSize> {
+        Ok(self.input.size())
+    }
+
+    fn event_reader(&self) -> EventReader {
+        self.reader.clone()
+    }
+
+    fn poll<F: Fn(&Event) -> bool>(
+        &self,
+        filter: F,
+        timeout: Option<Duration>,
+    ) -> io::Result<bool> {
+        self.reader.poll(timeout, filter)
+    }
+
+    fn read<F: Fn(&Event) -> bool>(&self, filter: F) -> io::Result<Event> {
+        self.reader.read(filter)
+    }
+
+    /// Does nothing: there is no real terminal to restore when panicking.
+    fn set_panic_hook(&mut self, _f: impl Fn(&mut PlatformHandle) + Send + Sync + 'static) {}
+}
+
+impl io::Write for VirtualTerminal {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resize_and_close() {
+        let terminal = VirtualTerminal::new(WindowSize::TEST);
+        let input = terminal.input();
+
+        let resized = WindowSize {
+            cols: 100,
+            ..WindowSize::TEST
+        };
+        input.resize(resized);
+        assert_eq!(terminal.get_dimensions().unwrap(), resized);
+        assert_eq!(
+            terminal.read(|_| true).unwrap(),
+            Event::WindowResized(resized)
+        );
+
+        input.close();
+        // Input sent after closing is ignored.
+        input.send_bytes(b"q");
+        input.resize(WindowSize::TEST);
+        assert_eq!(terminal.get_dimensions().unwrap(), resized);
+        assert_eq!(terminal.read(|_| true).unwrap(), Event::Closed);
+        assert_eq!(
+            terminal.poll(|_| true, None).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
@@ -18,7 +18,7 @@ use windows_sys::Win32::{
 
 use crate::{event::source::WindowsEventSource, Event, EventReader, OneBased, WindowSize};
 
-use super::Terminal;
+use super::{Terminal, TerminalMode};
 
 macro_rules! bail {
     ($msg:literal $(,)?) => {
@@ -379,26 +379,57 @@ impl WindowsTerminal {
             has_panic_hook: false,
         })
     }
+
+    /// Sets the console modes of the terminal. This is a more flexible version of
+    /// `Terminal::enter_raw_mode`.
+    ///
+    /// Windows consoles only echo input in canonical mode and have no equivalent of the read
+    /// minimum and timeout, which are ignored. `Terminal::enter_cooked_mode` restores the usual
+    /// console modes.
+    pub fn set_mode(&mut self, mode: TerminalMode) -> io::Result<()> {
+        let mut output_mode = self.output.get_mut().get_mode()?;
+        if mode.has_output_processing() {
+            output_mode &= !Console::DISABLE_NEWLINE_AUTO_RETURN;
+        } else {
+            output_mode |= Console::DISABLE_NEWLINE_AUTO_RETURN;
+        }
+        self.output.get_mut().set_mode(output_mode).ok();
+
+        let mut input_mode = self.input.get_mode()?
+            & !(Console::ENABLE_ECHO_INPUT
+                | Console::ENABLE_LINE_INPUT
+                | Console::ENABLE_PROCESSED_INPUT
+                | Console::ENABLE_MOUSE_INPUT
+                | Console::ENABLE_WINDOW_INPUT);
+        if mode.is_canonical() {
+            input_mode |= Console::ENABLE_LINE_INPUT;
+            if mode.is_echo() {
+                input_mode |= Console::ENABLE_ECHO_INPUT;
+            }
+        } else {
+            input_mode |= Console::ENABLE_MOUSE_INPUT | Console::ENABLE_WINDOW_INPUT;
+        }
+        if mode.has_signals() {
+            input_mode |= Console::ENABLE_PROCESSED_INPUT;
+        }
+        self.input.set_mode(input_mode)
+    }
+
+    /// Reads the terminal's current console modes.
+    pub fn mode(&self) -> io::Result<TerminalMode> {
+        let output_mode = self.output.get_ref().get_mode()?;
+        let input_mode = self.input.get_mode()?;
+        Ok(TerminalMode::raw()
+            .echo(input_mode & Console::ENABLE_ECHO_INPUT != 0)
+            .canonical(input_mode & Console::ENABLE_LINE_INPUT != 0)
+            .signals(input_mode & Console::ENABLE_PROCESSED_INPUT != 0)
+            .output_processing(output_mode & Console::DISABLE_NEWLINE_AUTO_RETURN == 0))
+    }
 }
 
 impl Terminal for WindowsTerminal {
     fn enter_raw_mode(&mut self) -> io::Result<()> {
-        let mode = self.output.get_mut().get_mode()?;
-        self.output
-            .get_mut()
-            .set_mode(mode | Console::DISABLE_NEWLINE_AUTO_RETURN)
-            .ok();
-        let mode = self.input.get_mode()?;
-        self.input.set_mode(
-            (mode
-                & !(Console::ENABLE_ECHO_INPUT
-                    | Console::ENABLE_LINE_INPUT
-                    | Console::ENABLE_PROCESSED_INPUT))
-                | Console::ENABLE_MOUSE_INPUT
-                | Console::ENABLE_WINDOW_INPUT,
-        )?;
-
-        Ok(())
+        self.set_mode(TerminalMode::raw())
     }
 
     fn enter_cooked_mode(&mut self) -> io::Result<()> {